@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+
+use lumen::math::*;
+use lumen::objects::*;
+use lumen::rendering::*;
+use lumen::Material;
+
+// same layout as the scene in main.rs, with a configurable grid size
+fn make_cube_array(n: usize) -> ObjectList {
+    let mut objects = ObjectList::new();
+    for i in 0..n {
+        for j in 0..n {
+            let (cx, cy) = (3.0 * i as f64, 3.0 * j as f64);
+            let mat = Material::new_metal(0.5, 0.5, 0.7, 0.0);
+            objects.add_cube(1.0, cx, 0.5, cy, mat);
+        }
+    }
+    let ground = Material::new_diffuse(0.5, 0.5, 0.5);
+    objects.add_sphere(1000.0, 0.0, -1000.0, 0.0, ground);
+    objects
+}
+
+fn camera_rays(n: usize) -> Vec<Ray> {
+    let extent = 3.0 * (n as f64 - 1.0);
+    let camera = CameraBuilder::new()
+        .resolution(256, 144)
+        .vfov(90.0)
+        .target(extent / 2.0, 0.0, extent / 2.0)
+        .position(extent / 2.0 + 2.0, extent / 2.0 + 2.0, extent / 2.0 + 2.0)
+        .upward(0.0, 1.0, 0.0)
+        .samples(1)
+        .max_depth(1)
+        .build()
+        .expect("bench camera is valid");
+    let mut rays = Vec::with_capacity(camera.image_width * camera.image_height);
+    for i in 0..camera.image_height {
+        for j in 0..camera.image_width {
+            let pixel = camera.pixel_origin
+                + (camera.pixel_delta_v * i as f64)
+                + (camera.pixel_delta_u * j as f64);
+            rays.push(Ray::new(camera.position, pixel - camera.position));
+        }
+    }
+    rays
+}
+
+fn time_hits<P: Physical>(world: &P, rays: &[Ray]) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut hits = 0;
+    for ray in rays {
+        let mut record = HitRecord::default();
+        let rt = Interval::new(0.001, f64::INFINITY);
+        if world.hit(ray, &rt, &mut record) {
+            hits += 1;
+        }
+    }
+    (start.elapsed(), hits)
+}
+
+fn main() {
+    for n in [4, 16, 64] {
+        let objects = make_cube_array(n);
+        let rays = camera_rays(n);
+
+        let build = Instant::now();
+        let bvh = ObjectBvh::new(objects.clone());
+        let build_time = build.elapsed();
+
+        let (linear, linear_hits) = time_hits(&objects, &rays);
+        let (accelerated, bvh_hits) = time_hits(&bvh, &rays);
+        assert_eq!(linear_hits, bvh_hits);
+
+        println!("{n}x{n} cube array, {} objects", objects.objects.len());
+        println!("  bvh:    {}", bvh.stats());
+        println!("  build:  {:?}", build_time);
+        println!("  linear: {:?} for {} rays", linear, rays.len());
+        println!("  bvh:    {:?} for {} rays", accelerated, rays.len());
+        println!(
+            "  speedup {:.1}x",
+            linear.as_secs_f64() / accelerated.as_secs_f64()
+        );
+    }
+}
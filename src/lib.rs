@@ -1,9 +1,11 @@
 // modules
+pub mod loaders;
 pub mod materials;
 pub mod math;
 pub mod objects;
 pub mod rendering;
 pub mod runtime;
+pub mod textures;
 
 // flatten
 pub use materials::Material;
@@ -11,4 +13,5 @@ pub use objects::ObjectList;
 pub use rendering::Camera;
 pub use rendering::CameraBuilder;
 pub use rendering::Renderer;
+pub use textures::Texture;
 pub use runtime::*;
@@ -0,0 +1,75 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(PathBuf, io::Error),
+    Format(PathBuf, String),
+    Parse {
+        file: String,
+        line: usize,
+        message: String,
+    },
+    // invalid value in a structured file, at a dotted field path
+    Field {
+        file: String,
+        field: String,
+        message: String,
+    },
+}
+
+impl LoadError {
+    pub fn format(path: &Path, message: &str) -> Self {
+        Self::Format(path.to_path_buf(), message.to_string())
+    }
+
+    pub fn parse(file: &str, line: usize, message: String) -> Self {
+        Self::Parse {
+            file: file.to_string(),
+            line,
+            message,
+        }
+    }
+
+    pub fn field(file: &str, field: &str, message: String) -> Self {
+        Self::Field {
+            file: file.to_string(),
+            field: field.to_string(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => {
+                write!(f, "{}: {}", path.display(), err)
+            }
+            Self::Format(path, message) => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+            Self::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+            Self::Field {
+                file,
+                field,
+                message,
+            } => write!(f, "{}: {}: {}", file, field, message),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Some(err),
+            Self::Format(..) | Self::Parse { .. } | Self::Field { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,16 @@
+// modules
+pub mod error;
+pub mod mtl;
+pub mod obj;
+pub mod scene;
+mod statement;
+
+// flatten
+pub use error::LoadError;
+pub use mtl::parse_mtl;
+pub use obj::load_obj;
+pub use obj::parse_obj;
+pub use scene::load_scene;
+pub use scene::parse_scene;
+pub use scene::save_scene;
+pub use scene::Scene;
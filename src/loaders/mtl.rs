@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use crate::materials::Material;
+use crate::math::Vec3;
+
+use super::statement::Statement;
+use super::LoadError;
+
+// raw mtl properties, converted into a Material once the block ends
+struct MtlEntry {
+    name: String,
+    kd: Vec3,
+    // unset is not the same as black, see to_material
+    ks: Option<Vec3>,
+    ke: Vec3,
+    ns: Option<f64>,
+    ni: f64,
+    illum: u32,
+}
+
+impl MtlEntry {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            kd: Vec3::new(0.8, 0.8, 0.8),
+            ks: None,
+            ke: Vec3::default(),
+            ns: None,
+            ni: 1.5,
+            illum: 2,
+        }
+    }
+
+    // any emission turns the material into a light, otherwise
+    // illumination models 3, 5 and 8 are ray traced reflection, 4, 6, 7
+    // and 9 involve refraction, everything else is treated as diffuse.
+    // reflective materials without a Ks take their color from Kd
+    fn to_material(&self) -> Material {
+        if !self.ke.near_zero() {
+            return Material::new_emissive(
+                self.ke.x, self.ke.y, self.ke.z, 1.0,
+            );
+        }
+        match self.illum {
+            3 | 5 | 8 => {
+                let fuzz = match self.ns {
+                    Some(ns) => (1.0 - ns / 1000.0).clamp(0.0, 1.0),
+                    None => 0.0,
+                };
+                let albedo = self.ks.unwrap_or(self.kd);
+                Material::new_metal(albedo.x, albedo.y, albedo.z, fuzz)
+            }
+            4 | 6 | 7 | 9 => Material::new_dielectric(self.ni),
+            _ => Material::new_diffuse(self.kd.x, self.kd.y, self.kd.z),
+        }
+    }
+}
+
+fn color(stmt: &Statement) -> Result<Vec3, LoadError> {
+    let c = stmt.floats(1, 3)?;
+    match c.len() {
+        1 => Ok(Vec3::new(c[0], c[0], c[0])),
+        3 => Ok(Vec3::new(c[0], c[1], c[2])),
+        _ => Err(stmt.error(format!(
+            "'{}' expects 1 or 3 numbers, found {}",
+            stmt.keyword,
+            c.len()
+        ))),
+    }
+}
+
+pub fn parse_mtl(
+    file: &str,
+    text: &str,
+) -> Result<HashMap<String, Material>, LoadError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<MtlEntry> = None;
+    for stmt in Statement::parse_all(file, text) {
+        if stmt.keyword == "newmtl" {
+            if let Some(entry) = current.take() {
+                materials.insert(entry.name.clone(), entry.to_material());
+            }
+            current = Some(MtlEntry::new(stmt.name()?));
+            continue;
+        }
+        let Some(entry) = current.as_mut() else {
+            return Err(stmt.error(format!(
+                "'{}' appears before any 'newmtl'",
+                stmt.keyword
+            )));
+        };
+        match stmt.keyword {
+            "Kd" => entry.kd = color(&stmt)?,
+            "Ks" => entry.ks = Some(color(&stmt)?),
+            "Ke" => entry.ke = color(&stmt)?,
+            "Ns" => entry.ns = Some(stmt.float()?),
+            "Ni" => entry.ni = stmt.float()?,
+            "illum" => {
+                let value = stmt.float()?;
+                if value < 0.0 || value.fract() != 0.0 {
+                    return Err(stmt.error(format!(
+                        "invalid illumination model '{}'",
+                        stmt.args[0]
+                    )));
+                }
+                entry.illum = value as u32;
+            }
+            // ambient, transparency and texture maps have no equivalent in
+            // the material model and are skipped
+            _ => {}
+        }
+    }
+    if let Some(entry) = current.take() {
+        materials.insert(entry.name.clone(), entry.to_material());
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textures::TextureValue;
+
+    fn metal_albedo(text: &str) -> Vec3 {
+        let materials = parse_mtl("test.mtl", text).unwrap();
+        match &materials["shiny"] {
+            Material::Metal(metal) => {
+                metal.albedo.value(0.0, 0.0, Vec3::default())
+            }
+            other => panic!("expected a metal, found {other:?}"),
+        }
+    }
+
+    #[test]
+    fn reflective_without_ks_uses_kd() {
+        let text = "newmtl shiny\nKd 0.9 0.6 0.2\nillum 3\n";
+        assert_eq!(metal_albedo(text), Vec3::new(0.9, 0.6, 0.2));
+    }
+
+    #[test]
+    fn reflective_with_ks_uses_ks() {
+        let text = "newmtl shiny\nKd 0.9 0.6 0.2\nKs 0.5\nillum 5\n";
+        assert_eq!(metal_albedo(text), Vec3::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn errors_carry_line_numbers() {
+        let text = "# header\nnewmtl a\nKd 1 2\n";
+        let err = parse_mtl("test.mtl", text).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "test.mtl:3: 'Kd' expects 1 or 3 numbers, found 2"
+        );
+    }
+}
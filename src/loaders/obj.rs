@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::materials::Material;
+use crate::math::Vec3;
+use crate::objects::{FaceIndex, Mesh, Object, ObjectList};
+
+use super::mtl::parse_mtl;
+use super::statement::Statement;
+use super::LoadError;
+
+struct ObjParser {
+    vertices: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    groups: Vec<(Material, Vec<[FaceIndex; 3]>)>,
+    faces: Vec<[FaceIndex; 3]>,
+    mat: Material,
+    materials: HashMap<String, Material>,
+}
+
+impl ObjParser {
+    fn new(materials: HashMap<String, Material>) -> Self {
+        Self {
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            groups: Vec::new(),
+            faces: Vec::new(),
+            mat: Material::new_diffuse(0.8, 0.8, 0.8),
+            materials,
+        }
+    }
+
+    // every object, group and material change starts a new mesh, all of
+    // which share the same vertex buffers
+    fn end_group(&mut self) {
+        if !self.faces.is_empty() {
+            let faces = std::mem::take(&mut self.faces);
+            self.groups.push((self.mat.clone(), faces));
+        }
+    }
+
+    fn resolve(
+        stmt: &Statement,
+        token: &str,
+        idx: &str,
+        len: usize,
+    ) -> Result<usize, LoadError> {
+        let invalid = || stmt.error(format!("invalid index in '{}'", token));
+        let i = idx.parse::<i64>().map_err(|_| invalid())?;
+        let resolved = match i {
+            0 => return Err(invalid()),
+            i if i > 0 => i - 1,
+            i => len as i64 + i,
+        };
+        if resolved < 0 || resolved >= len as i64 {
+            return Err(stmt.error(format!(
+                "index {} in '{}' is out of range, {} defined",
+                i, token, len
+            )));
+        }
+        Ok(resolved as usize)
+    }
+
+    fn corner(
+        &self,
+        stmt: &Statement,
+        token: &str,
+    ) -> Result<FaceIndex, LoadError> {
+        let mut parts = token.split('/');
+        let v = parts.next().unwrap_or("");
+        let vt = parts.next().filter(|s| !s.is_empty());
+        let vn = parts.next().filter(|s| !s.is_empty());
+        if parts.next().is_some() {
+            return Err(stmt.error(format!("malformed vertex '{}'", token)));
+        }
+        let vertex = Self::resolve(stmt, token, v, self.vertices.len())?;
+        let uv = match vt {
+            Some(i) => Some(Self::resolve(stmt, token, i, self.uvs.len())?),
+            None => None,
+        };
+        let normal = match vn {
+            Some(i) => Some(Self::resolve(stmt, token, i, self.normals.len())?),
+            None => None,
+        };
+        Ok(FaceIndex::new(vertex, normal, uv))
+    }
+
+    fn face(&mut self, stmt: &Statement) -> Result<(), LoadError> {
+        if stmt.args.len() < 3 {
+            return Err(stmt.error(format!(
+                "face needs at least 3 vertices, found {}",
+                stmt.args.len()
+            )));
+        }
+        let corners = stmt
+            .args
+            .iter()
+            .map(|token| self.corner(stmt, token))
+            .collect::<Result<Vec<FaceIndex>, LoadError>>()?;
+        // fan triangulation, exact for the convex polygons exporters emit
+        for i in 1..corners.len() - 1 {
+            self.faces.push([corners[0], corners[i], corners[i + 1]]);
+        }
+        Ok(())
+    }
+
+    fn statement<F>(
+        &mut self,
+        stmt: &Statement,
+        libraries: &mut F,
+    ) -> Result<(), LoadError>
+    where
+        F: FnMut(&Statement) -> Result<HashMap<String, Material>, LoadError>,
+    {
+        match stmt.keyword {
+            "v" => {
+                let v = stmt.floats(3, 7)?;
+                self.vertices.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "vn" => self.normals.push(stmt.vec3()?),
+            "vt" => {
+                let t = stmt.floats(1, 3)?;
+                self.uvs.push((t[0], t.get(1).copied().unwrap_or(0.0)));
+            }
+            "f" => self.face(stmt)?,
+            "g" | "o" => self.end_group(),
+            "usemtl" => {
+                let name = stmt.name()?;
+                let Some(mat) = self.materials.get(&name) else {
+                    return Err(
+                        stmt.error(format!("unknown material '{}'", name))
+                    );
+                };
+                let mat = mat.clone();
+                self.end_group();
+                self.mat = mat;
+            }
+            "mtllib" => {
+                let materials = libraries(stmt)?;
+                self.materials.extend(materials);
+            }
+            // smoothing groups, lines, points and free-form geometry are
+            // not supported and ignored
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> ObjectList {
+        self.end_group();
+        let vertices = Arc::new(self.vertices);
+        let normals = Arc::new(self.normals);
+        let uvs = Arc::new(self.uvs);
+        let mut objects = ObjectList::new();
+        for (mat, faces) in self.groups {
+            let mesh = Mesh::new(
+                Arc::clone(&vertices),
+                Arc::clone(&normals),
+                Arc::clone(&uvs),
+                faces,
+                mat,
+            );
+            // every index was range checked as its face was parsed
+            objects.add_mesh(mesh.expect("face indexes are in range"));
+        }
+        objects
+    }
+}
+
+fn parse<F>(
+    file: &str,
+    text: &str,
+    materials: HashMap<String, Material>,
+    mut libraries: F,
+) -> Result<ObjectList, LoadError>
+where
+    F: FnMut(&Statement) -> Result<HashMap<String, Material>, LoadError>,
+{
+    let mut parser = ObjParser::new(materials);
+    for stmt in Statement::parse_all(file, text) {
+        parser.statement(&stmt, &mut libraries)?;
+    }
+    Ok(parser.finish())
+}
+
+// parses obj source using only the given materials, mtllib statements
+// are ignored
+pub fn parse_obj(
+    file: &str,
+    text: &str,
+    materials: &HashMap<String, Material>,
+) -> Result<ObjectList, LoadError> {
+    parse(file, text, materials.clone(), |_| Ok(HashMap::new()))
+}
+
+// loads an obj file along with any material libraries it references,
+// which are resolved relative to the obj file
+pub fn load_obj(path: impl AsRef<Path>) -> Result<ObjectList, LoadError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .map_err(|err| LoadError::Io(path.to_path_buf(), err))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let file = path.display().to_string();
+    let mut objects = parse(&file, &text, HashMap::new(), |stmt| {
+        if stmt.args.is_empty() {
+            return Err(stmt.error("'mtllib' needs a file name".to_string()));
+        }
+        // several libraries may share a line, but a file name with spaces
+        // in it is taken whole when such a file exists
+        let whole = stmt.args.join(" ");
+        let names = match stmt.args.len() > 1 && dir.join(&whole).is_file() {
+            true => vec![whole.as_str()],
+            false => stmt.args.clone(),
+        };
+        let mut materials = HashMap::new();
+        for name in names {
+            let mtl_path = dir.join(name);
+            let mtl_text = fs::read_to_string(&mtl_path)
+                .map_err(|err| LoadError::Io(mtl_path.clone(), err))?;
+            let mtl_file = mtl_path.display().to_string();
+            materials.extend(parse_mtl(&mtl_file, &mtl_text)?);
+        }
+        Ok(materials)
+    })?;
+    for object in &mut objects.objects {
+        if let Object::Mesh(mesh) = object {
+            mesh.path = Some(path.to_path_buf());
+        }
+    }
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn faces(objects: &ObjectList) -> Vec<[usize; 3]> {
+        objects
+            .objects
+            .iter()
+            .flat_map(|object| match object {
+                Object::Mesh(mesh) => mesh.faces().to_vec(),
+                _ => panic!("obj files only produce meshes"),
+            })
+            .map(|face| face.map(|corner| corner.vertex))
+            .collect()
+    }
+
+    fn parse(text: &str) -> Result<ObjectList, LoadError> {
+        parse_obj("test.obj", text, &HashMap::new())
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let text = format!("{SQUARE}v 0.5 1.5 0\nf 1 2 3 5 4\n");
+        let objects = parse(&text).unwrap();
+        assert_eq!(faces(&objects), [[0, 1, 2], [0, 2, 4], [0, 4, 3]]);
+    }
+
+    #[test]
+    fn negative_indexes_count_back_from_the_last_vertex() {
+        let text = format!("{SQUARE}f -4 -3 -2\nf 1/ -2 -1\n");
+        let objects = parse(&text).unwrap();
+        assert_eq!(faces(&objects), [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn malformed_statements_report_their_line() {
+        let err = parse(&format!("{SQUARE}\nf 1 2\n")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "test.obj:6: face needs at least 3 vertices, found 2"
+        );
+        let err = parse(&format!("{SQUARE}f 1 2 5\n")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "test.obj:5: index 5 in '5' is out of range, 4 defined"
+        );
+        let err = parse("v 0 0\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "test.obj:1: 'v' expects 3 to 7 numbers, found 2"
+        );
+        let err = parse("v 0 zero 0\n").unwrap_err();
+        assert_eq!(err.to_string(), "test.obj:1: invalid number 'zero' in 'v'");
+    }
+
+    #[test]
+    fn mtllib_names_may_contain_spaces() {
+        let dir =
+            env::temp_dir().join(format!("lumen-obj-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("my materials.mtl"), "newmtl red\nKd 1 0 0\n")
+            .unwrap();
+        let text =
+            format!("mtllib my materials.mtl\n{SQUARE}usemtl red\nf 1 2 3\n");
+        fs::write(dir.join("model.obj"), text).unwrap();
+        let objects = load_obj(dir.join("model.obj"));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(faces(&objects.unwrap()), [[0, 1, 2]]);
+    }
+}
@@ -0,0 +1,1148 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use toml::{Table, Value};
+
+use crate::materials::Material;
+use crate::math::Vec3;
+use crate::objects::objects::Object;
+use crate::objects::{FaceIndex, Mesh, ObjectList, Triangle};
+use crate::rendering::camera::Lens;
+use crate::rendering::filter::{GAUSSIAN_SIGMA, MITCHELL_B, MITCHELL_C};
+use crate::rendering::{
+    Background, Camera, CameraBuilder, CameraError, Filter, Projection, Stereo,
+    StereoMode,
+};
+use crate::textures::{ImageTexture, NoiseKind, Texture};
+
+use super::load_obj;
+use super::LoadError;
+
+const NOISE_DEPTH: usize = 7;
+
+// everything needed to render, as described by a scene file
+pub struct Scene {
+    pub objects: ObjectList,
+    pub camera: Camera,
+    // settings the camera was built from, kept so the scene can be saved
+    pub builder: CameraBuilder,
+    pub background: Background,
+    // named materials, reused by name when the scene is saved
+    pub materials: Vec<(String, Material)>,
+}
+
+impl Scene {
+    pub fn new(
+        objects: ObjectList,
+        builder: CameraBuilder,
+        background: Background,
+    ) -> Result<Self, CameraError> {
+        Ok(Self {
+            objects,
+            camera: builder.build()?,
+            builder,
+            background,
+            materials: Vec::new(),
+        })
+    }
+
+    // file paths are written relative to the working directory
+    pub fn to_toml(&self) -> Result<String, LoadError> {
+        serialize("<scene>", self, Path::new(""))
+    }
+}
+
+// a toml value along with its dotted path, for error messages
+struct Node<'a> {
+    file: &'a str,
+    path: String,
+    value: &'a Value,
+}
+
+impl<'a> Node<'a> {
+    fn error(&self, message: impl Into<String>) -> LoadError {
+        let path = match self.path.is_empty() {
+            true => "<root>",
+            false => &self.path,
+        };
+        LoadError::field(self.file, path, message.into())
+    }
+
+    fn child(&self, key: &str, value: &'a Value) -> Node<'a> {
+        let path = match self.path.is_empty() {
+            true => key.to_string(),
+            false => format!("{}.{}", self.path, key),
+        };
+        Node {
+            file: self.file,
+            path,
+            value,
+        }
+    }
+
+    fn mismatch(&self, expected: &str) -> LoadError {
+        let found = self.value.type_str();
+        self.error(format!("expected {expected}, found {found}"))
+    }
+
+    fn table(&self) -> Result<&'a Table, LoadError> {
+        self.value
+            .as_table()
+            .ok_or_else(|| self.mismatch("a table"))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Node<'a>>, LoadError> {
+        Ok(self.table()?.get(key).map(|v| self.child(key, v)))
+    }
+
+    fn field(&self, key: &str) -> Result<Node<'a>, LoadError> {
+        self.get(key)?
+            .ok_or_else(|| self.error(format!("missing field '{key}'")))
+    }
+
+    // rejects keys outside the allowed set so typos do not go unnoticed
+    fn expect_keys(&self, allowed: &[&str]) -> Result<(), LoadError> {
+        for (key, value) in self.table()? {
+            if !allowed.contains(&key.as_str()) {
+                let expected = allowed.join(", ");
+                let message = format!("unknown field, expected {expected}");
+                return Err(self.child(key, value).error(message));
+            }
+        }
+        Ok(())
+    }
+
+    fn float(&self) -> Result<f64, LoadError> {
+        match self.value {
+            Value::Float(f) => Ok(*f),
+            Value::Integer(i) => Ok(*i as f64),
+            _ => Err(self.mismatch("a number")),
+        }
+    }
+
+    fn usize(&self) -> Result<usize, LoadError> {
+        match self.value {
+            Value::Integer(i) if *i >= 0 => Ok(*i as usize),
+            _ => Err(self.mismatch("a non-negative integer")),
+        }
+    }
+
+    fn str(&self) -> Result<&'a str, LoadError> {
+        self.value.as_str().ok_or_else(|| self.mismatch("a string"))
+    }
+
+    fn array(&self) -> Result<Vec<Node<'a>>, LoadError> {
+        let items = self
+            .value
+            .as_array()
+            .ok_or_else(|| self.mismatch("an array"))?;
+        let node = |(i, value)| Node {
+            file: self.file,
+            path: format!("{}[{}]", self.path, i),
+            value,
+        };
+        Ok(items.iter().enumerate().map(node).collect())
+    }
+
+    fn fixed<T, F, const N: usize>(&self, f: F) -> Result<[T; N], LoadError>
+    where
+        T: Copy + Default,
+        F: Fn(&Node<'a>) -> Result<T, LoadError>,
+    {
+        let items = self.array()?;
+        if items.len() != N {
+            let message = format!("expected {N} values, found {}", items.len());
+            return Err(self.error(message));
+        }
+        let mut out = [T::default(); N];
+        for (slot, item) in out.iter_mut().zip(&items) {
+            *slot = f(item)?;
+        }
+        Ok(out)
+    }
+
+    fn vec3(&self) -> Result<Vec3, LoadError> {
+        let [x, y, z] = self.fixed(Node::float)?;
+        Ok(Vec3::new(x, y, z))
+    }
+
+    fn uv(&self) -> Result<(f64, f64), LoadError> {
+        let [u, v] = self.fixed(Node::float)?;
+        Ok((u, v))
+    }
+
+    fn list<T, F>(&self, f: F) -> Result<Vec<T>, LoadError>
+    where
+        F: Fn(&Node<'a>) -> Result<T, LoadError>,
+    {
+        self.array()?.iter().map(f).collect()
+    }
+
+    // enum like values are a bare name or a table with a type field
+    fn variant(&self) -> Result<(&'a str, Option<&Self>), LoadError> {
+        match self.value {
+            Value::String(name) => Ok((name, None)),
+            Value::Table(_) => Ok((self.field("type")?.str()?, Some(self))),
+            _ => Err(self.mismatch("a name or a table")),
+        }
+    }
+
+    fn unknown(&self, kind: &str, expected: &str) -> LoadError {
+        let node = self.get("type").ok().flatten();
+        let node = node.as_ref().unwrap_or(self);
+        node.error(format!("unknown type '{kind}', expected {expected}"))
+    }
+}
+
+fn optional<'a, T, F>(
+    node: &Node<'a>,
+    key: &str,
+    f: F,
+) -> Result<Option<T>, LoadError>
+where
+    F: Fn(&Node<'a>) -> Result<T, LoadError>,
+{
+    node.get(key)?.map(|n| f(&n)).transpose()
+}
+
+fn camera(node: &Node) -> Result<CameraBuilder, LoadError> {
+    node.expect_keys(&[
+        "resolution",
+        "vfov",
+        "position",
+        "target",
+        "up",
+        "samples",
+        "max_depth",
+        "aperture",
+        "defocus_angle",
+        "focus_distance",
+        "aspect_ratio",
+        "filter",
+        "projection",
+        "stereo",
+    ])?;
+    // unset fields keep the builder defaults, which also reports missing
+    // required ones
+    let mut builder = CameraBuilder::new();
+    if let Some([w, h]) =
+        optional(node, "resolution", |n| n.fixed(Node::usize))?
+    {
+        builder.resolution(w, h);
+    }
+    if let Some(p) = optional(node, "position", Node::vec3)? {
+        builder.position(p.x, p.y, p.z);
+    }
+    if let Some(t) = optional(node, "target", Node::vec3)? {
+        builder.target(t.x, t.y, t.z);
+    }
+    if let Some(up) = optional(node, "up", Node::vec3)? {
+        builder.upward(up.x, up.y, up.z);
+    }
+    if let Some(samples) = optional(node, "samples", Node::usize)? {
+        builder.samples(samples);
+    }
+    if let Some(depth) = optional(node, "max_depth", Node::usize)? {
+        builder.max_depth(depth);
+    }
+    if let Some(projection) = optional(node, "projection", projection)? {
+        builder.projection(projection);
+    }
+    if let Some(vfov) = optional(node, "vfov", Node::float)? {
+        builder.vfov(vfov);
+    }
+    let aperture = optional(node, "aperture", Node::float)?;
+    let angle = optional(node, "defocus_angle", Node::float)?;
+    match (aperture, angle) {
+        (Some(_), Some(_)) => {
+            let message = "set either aperture or defocus_angle, not both";
+            return Err(node.error(message));
+        }
+        (Some(aperture), None) => {
+            builder.aperture(aperture);
+        }
+        (None, Some(angle)) => {
+            builder.defocus_angle(angle);
+        }
+        (None, None) => {}
+    }
+    if let Some(distance) = optional(node, "focus_distance", Node::float)? {
+        builder.focus_distance(distance);
+    }
+    if let Some(ratio) = optional(node, "aspect_ratio", Node::float)? {
+        builder.aspect_ratio(ratio);
+    }
+    if let Some(filter) = optional(node, "filter", filter)? {
+        builder.filter(filter);
+    }
+    if let Some(s) = optional(node, "stereo", stereo)? {
+        builder.stereo(s.mode, s.separation, s.convergence);
+    }
+    Ok(builder)
+}
+
+fn projection(node: &Node) -> Result<Projection, LoadError> {
+    let (kind, table) = node.variant()?;
+    let keys: &[&str] = match kind {
+        "orthographic" => &["type", "height"],
+        "fisheye" => &["type", "fov"],
+        _ => &["type"],
+    };
+    if let Some(table) = table {
+        table.expect_keys(keys)?;
+    }
+    let param = |key: &str| match table {
+        Some(table) => table.field(key)?.float(),
+        None => Err(node.error(format!("{kind} needs a '{key}' field"))),
+    };
+    match kind {
+        "perspective" => Ok(Projection::Perspective),
+        "orthographic" => Ok(Projection::Orthographic {
+            height: param("height")?,
+        }),
+        "equirectangular" => Ok(Projection::Equirectangular),
+        "fisheye" => Ok(Projection::Fisheye { fov: param("fov")? }),
+        "cube_map" => Ok(Projection::CubeMap),
+        _ => Err(node.unknown(
+            kind,
+            "perspective, orthographic, equirectangular, fisheye or cube_map",
+        )),
+    }
+}
+
+fn filter(node: &Node) -> Result<Filter, LoadError> {
+    let (kind, table) = node.variant()?;
+    let keys: &[&str] = match kind {
+        "gaussian" => &["type", "sigma"],
+        "mitchell" => &["type", "b", "c"],
+        _ => &["type"],
+    };
+    if let Some(table) = table {
+        table.expect_keys(keys)?;
+    }
+    let param = |key: &str, default: f64| match table {
+        Some(table) => {
+            Ok(optional(table, key, Node::float)?.unwrap_or(default))
+        }
+        None => Ok(default),
+    };
+    match kind {
+        "box" => Ok(Filter::Box),
+        "tent" => Ok(Filter::Tent),
+        "gaussian" => Ok(Filter::Gaussian {
+            sigma: param("sigma", GAUSSIAN_SIGMA)?,
+        }),
+        "mitchell" => Ok(Filter::Mitchell {
+            b: param("b", MITCHELL_B)?,
+            c: param("c", MITCHELL_C)?,
+        }),
+        _ => Err(node.unknown(kind, "box, tent, gaussian or mitchell")),
+    }
+}
+
+fn stereo(node: &Node) -> Result<Stereo, LoadError> {
+    node.expect_keys(&["mode", "separation", "convergence"])?;
+    let mode = match optional(node, "mode", Node::str)? {
+        None | Some("parallel") => StereoMode::Parallel,
+        Some("toe_in") => StereoMode::ToeIn,
+        Some(other) => {
+            let message =
+                format!("unknown mode '{other}', expected parallel or toe_in");
+            return Err(node.field("mode")?.error(message));
+        }
+    };
+    Ok(Stereo {
+        mode,
+        separation: node.field("separation")?.float()?,
+        convergence: node.field("convergence")?.float()?,
+    })
+}
+
+fn background(node: &Node) -> Result<Background, LoadError> {
+    match node.value {
+        Value::String(name) if name == "none" => Ok(Background::None),
+        Value::Array(_) => Ok(Background::Solid(node.vec3()?)),
+        Value::Table(_) => {
+            node.expect_keys(&["from", "to"])?;
+            Ok(Background::Gradient {
+                from: node.field("from")?.vec3()?,
+                to: node.field("to")?.vec3()?,
+            })
+        }
+        _ => Err(node.mismatch("\"none\", a color or a gradient table")),
+    }
+}
+
+fn texture(node: &Node, dir: &Path) -> Result<Texture, LoadError> {
+    if let Value::Array(_) = node.value {
+        return Ok(Texture::from(node.vec3()?));
+    }
+    let (kind, _) = node.variant()?;
+    match kind {
+        "solid" => {
+            node.expect_keys(&["type", "color"])?;
+            Ok(Texture::from(node.field("color")?.vec3()?))
+        }
+        "checker" => {
+            node.expect_keys(&["type", "scale", "even", "odd"])?;
+            Ok(Texture::new_checker(
+                node.field("scale")?.float()?,
+                node.field("even")?.vec3()?,
+                node.field("odd")?.vec3()?,
+            ))
+        }
+        "noise" => {
+            node.expect_keys(&["type", "kind", "depth", "scale", "color"])?;
+            let depth =
+                optional(node, "depth", Node::usize)?.unwrap_or(NOISE_DEPTH);
+            let kind_node = node.field("kind")?;
+            let kind = match kind_node.str()? {
+                "perlin" => NoiseKind::Perlin,
+                "turbulence" => NoiseKind::Turbulence { depth },
+                "marble" => NoiseKind::Marble { depth },
+                other => {
+                    return Err(kind_node.error(format!(
+                        "unknown noise '{other}', expected perlin, \
+                         turbulence or marble"
+                    )))
+                }
+            };
+            let color = optional(node, "color", Node::vec3)?;
+            Ok(Texture::new_noise(
+                kind,
+                node.field("scale")?.float()?,
+                color.unwrap_or(Vec3::new(1.0, 1.0, 1.0)),
+            ))
+        }
+        "image" => {
+            node.expect_keys(&["type", "path"])?;
+            let path = dir.join(node.field("path")?.str()?);
+            Ok(Texture::new_image(ImageTexture::load(path)?))
+        }
+        _ => Err(node.unknown(kind, "solid, checker, noise or image")),
+    }
+}
+
+fn material(node: &Node, dir: &Path) -> Result<Material, LoadError> {
+    let kind = node.field("type")?.str()?;
+    match kind {
+        "diffuse" => {
+            node.expect_keys(&["type", "albedo"])?;
+            let albedo = texture(&node.field("albedo")?, dir)?;
+            Ok(Material::new_diffuse_texture(albedo))
+        }
+        "metal" => {
+            node.expect_keys(&["type", "albedo", "fuzz"])?;
+            let albedo = texture(&node.field("albedo")?, dir)?;
+            let fuzz = optional(node, "fuzz", Node::float)?.unwrap_or(0.0);
+            Ok(Material::new_metal_texture(albedo, fuzz))
+        }
+        "dielectric" => {
+            node.expect_keys(&["type", "refraction"])?;
+            let refraction = node.field("refraction")?.float()?;
+            Ok(Material::new_dielectric(refraction))
+        }
+        "emissive" => {
+            node.expect_keys(&["type", "color", "intensity"])?;
+            let color = texture(&node.field("color")?, dir)?;
+            let intensity =
+                optional(node, "intensity", Node::float)?.unwrap_or(1.0);
+            Ok(Material::new_emissive_texture(color, intensity))
+        }
+        _ => Err(node.unknown(kind, "diffuse, metal, dielectric or emissive")),
+    }
+}
+
+fn material_ref(
+    node: &Node,
+    materials: &HashMap<String, Material>,
+) -> Result<Material, LoadError> {
+    let name_node = node.field("material")?;
+    let name = name_node.str()?;
+    materials
+        .get(name)
+        .cloned()
+        .ok_or_else(|| name_node.error(format!("unknown material '{name}'")))
+}
+
+fn object(
+    node: &Node,
+    materials: &HashMap<String, Material>,
+    dir: &Path,
+    objects: &mut ObjectList,
+) -> Result<(), LoadError> {
+    let kind = node.field("type")?.str()?;
+    match kind {
+        "sphere" => {
+            node.expect_keys(&["type", "material", "center", "radius"])?;
+            let c = node.field("center")?.vec3()?;
+            let radius = node.field("radius")?.float()?;
+            let mat = material_ref(node, materials)?;
+            objects.add_sphere(radius, c.x, c.y, c.z, mat);
+        }
+        "cube" => {
+            node.expect_keys(&["type", "material", "corner", "length"])?;
+            let c = node.field("corner")?.vec3()?;
+            let length = node.field("length")?.float()?;
+            let mat = material_ref(node, materials)?;
+            objects.add_cube(length, c.x, c.y, c.z, mat);
+        }
+        "quad" => {
+            node.expect_keys(&["type", "material", "q", "u", "v"])?;
+            objects.add_quad(
+                node.field("q")?.vec3()?,
+                node.field("u")?.vec3()?,
+                node.field("v")?.vec3()?,
+                material_ref(node, materials)?,
+            );
+        }
+        "triangle" => {
+            node.expect_keys(&[
+                "type", "material", "a", "b", "c", "normals", "uvs",
+            ])?;
+            let mut tri = Triangle::new(
+                node.field("a")?.vec3()?,
+                node.field("b")?.vec3()?,
+                node.field("c")?.vec3()?,
+                material_ref(node, materials)?,
+            );
+            if let Some(n) = optional(node, "normals", |n| n.fixed(Node::vec3))?
+            {
+                tri = tri.with_normals(n);
+            }
+            if let Some(uvs) = optional(node, "uvs", |n| n.fixed(Node::uv))? {
+                tri = tri.with_uvs(uvs);
+            }
+            objects.objects.push(Object::Triangle(tri));
+        }
+        "mesh" if node.get("path")?.is_some() => {
+            node.expect_keys(&["type", "material", "path"])?;
+            let path = dir.join(node.field("path")?.str()?);
+            let mut loaded = load_obj(path)?;
+            // an explicit material replaces the ones from the mtl files
+            if node.get("material")?.is_some() {
+                let mat = material_ref(node, materials)?;
+                for obj in &mut loaded.objects {
+                    if let Object::Mesh(mesh) = obj {
+                        mesh.mat = mat.clone();
+                    }
+                }
+            }
+            objects.objects.extend(loaded.objects);
+        }
+        "mesh" => objects.add_mesh(mesh(node, materials)?),
+        _ => {
+            return Err(
+                node.unknown(kind, "sphere, cube, quad, triangle or mesh")
+            )
+        }
+    }
+    Ok(())
+}
+
+// inline meshes index into their own vertex, normal and uv lists
+fn mesh(
+    node: &Node,
+    materials: &HashMap<String, Material>,
+) -> Result<Mesh, LoadError> {
+    node.expect_keys(&[
+        "type",
+        "material",
+        "vertices",
+        "normals",
+        "uvs",
+        "faces",
+        "face_normals",
+        "face_uvs",
+    ])?;
+    let vertices = node.field("vertices")?.list(Node::vec3)?;
+    let normals = optional(node, "normals", |n| n.list(Node::vec3))?;
+    let uvs = optional(node, "uvs", |n| n.list(Node::uv))?;
+    let indexes = |n: &Node,
+                   len: usize|
+     -> Result<Vec<[usize; 3]>, LoadError> {
+        let faces = n.array()?;
+        let mut out = Vec::with_capacity(faces.len());
+        for face in faces {
+            let idx = face.fixed(Node::usize)?;
+            if let Some(&bad) = idx.iter().find(|&&i| i >= len) {
+                let message = format!("index {bad} is out of range 0..{len}");
+                return Err(face.error(message));
+            }
+            out.push(idx);
+        }
+        Ok(out)
+    };
+    let faces_node = node.field("faces")?;
+    let faces = indexes(&faces_node, vertices.len())?;
+    let per_face =
+        |key: &str, list: &str, len: Option<usize>| match node.get(key)? {
+            None => Ok(None),
+            Some(n) => {
+                let Some(len) = len else {
+                    return Err(n.error(format!("needs a '{list}' list")));
+                };
+                let idx = indexes(&n, len)?;
+                if idx.len() != faces.len() {
+                    let message =
+                        format!("expected one entry per face, {}", faces.len());
+                    return Err(n.error(message));
+                }
+                Ok(Some(idx))
+            }
+        };
+    let face_normals =
+        per_face("face_normals", "normals", normals.as_ref().map(Vec::len))?;
+    let face_uvs = per_face("face_uvs", "uvs", uvs.as_ref().map(Vec::len))?;
+    let faces = (0..faces.len())
+        .map(|f| {
+            [0, 1, 2].map(|k| {
+                FaceIndex::new(
+                    faces[f][k],
+                    face_normals.as_ref().map(|n| n[f][k]),
+                    face_uvs.as_ref().map(|t| t[f][k]),
+                )
+            })
+        })
+        .collect();
+    let mat = material_ref(node, materials)?;
+    Mesh::new(
+        Arc::new(vertices),
+        Arc::new(normals.unwrap_or_default()),
+        Arc::new(uvs.unwrap_or_default()),
+        faces,
+        mat,
+    )
+    .map_err(|err| node.error(err.to_string()))
+}
+
+// paths inside the scene are resolved relative to dir
+pub fn parse_scene(
+    file: &str,
+    text: &str,
+    dir: &Path,
+) -> Result<Scene, LoadError> {
+    let table: Table = text.parse().map_err(|err: toml::de::Error| {
+        let offset = err.span().map(|s| s.start).unwrap_or(0);
+        let line = text[..offset].matches('\n').count() + 1;
+        LoadError::parse(file, line, err.message().to_string())
+    })?;
+    let value = Value::Table(table);
+    let root = Node {
+        file,
+        path: String::new(),
+        value: &value,
+    };
+    root.expect_keys(&["camera", "background", "materials", "objects"])?;
+    let camera_node = root.field("camera")?;
+    let builder = camera(&camera_node)?;
+    let camera = builder
+        .build()
+        .map_err(|err| camera_node.error(err.to_string()))?;
+    let background =
+        optional(&root, "background", background)?.unwrap_or_default();
+    let mut named = Vec::new();
+    if let Some(node) = root.get("materials")? {
+        for (name, value) in node.table()? {
+            let mat = material(&node.child(name, value), dir)?;
+            named.push((name.clone(), mat));
+        }
+    }
+    let materials: HashMap<String, Material> = named.iter().cloned().collect();
+    let mut objects = ObjectList::new();
+    if let Some(node) = root.get("objects")? {
+        for obj in node.array()? {
+            object(&obj, &materials, dir, &mut objects)?;
+        }
+    }
+    Ok(Scene {
+        objects,
+        camera,
+        builder,
+        background,
+        materials: named,
+    })
+}
+
+pub fn load_scene(path: impl AsRef<Path>) -> Result<Scene, LoadError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .map_err(|err| LoadError::Io(path.to_path_buf(), err))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    parse_scene(&path.display().to_string(), &text, dir)
+}
+
+pub fn save_scene(
+    path: impl AsRef<Path>,
+    scene: &Scene,
+) -> Result<(), LoadError> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let text = serialize(&path.display().to_string(), scene, dir)?;
+    fs::write(path, text).map_err(|err| LoadError::Io(path.to_path_buf(), err))
+}
+
+fn vec3_value(v: Vec3) -> Value {
+    Value::Array(vec![v.x.into(), v.y.into(), v.z.into()])
+}
+
+fn uv_value((u, v): (f64, f64)) -> Value {
+    Value::Array(vec![u.into(), v.into()])
+}
+
+fn int_value(n: usize) -> Value {
+    Value::Integer(n as i64)
+}
+
+fn typed(kind: &str, fields: Vec<(&str, Value)>) -> Value {
+    let mut table = Table::new();
+    table.insert("type".to_string(), kind.into());
+    for (key, value) in fields {
+        table.insert(key.to_string(), value);
+    }
+    Value::Table(table)
+}
+
+fn camera_value(b: &CameraBuilder) -> Value {
+    let mut t = Table::new();
+    let mut put = |key: &str, value: Option<Value>| {
+        if let Some(value) = value {
+            t.insert(key.to_string(), value);
+        }
+    };
+    let resolution = b.image_width.zip(b.image_height);
+    put(
+        "resolution",
+        resolution.map(|(w, h)| Value::Array(vec![int_value(w), int_value(h)])),
+    );
+    put("vfov", b.vfov.map(Value::from));
+    put("position", b.position.map(vec3_value));
+    put("target", b.target.map(vec3_value));
+    put("up", b.up.map(vec3_value));
+    put("samples", b.samples.map(int_value));
+    put("max_depth", b.max_depth.map(int_value));
+    match b.lens {
+        Some(Lens::Aperture(d)) => put("aperture", Some(d.into())),
+        Some(Lens::DefocusAngle(a)) => put("defocus_angle", Some(a.into())),
+        None => {}
+    }
+    put("focus_distance", b.focus_distance.map(Value::from));
+    put("aspect_ratio", b.aspect_ratio.map(Value::from));
+    put(
+        "filter",
+        b.filter.map(|f| match f {
+            Filter::Box => "box".into(),
+            Filter::Tent => "tent".into(),
+            Filter::Gaussian { sigma } => {
+                typed("gaussian", vec![("sigma", sigma.into())])
+            }
+            Filter::Mitchell { b, c } => {
+                typed("mitchell", vec![("b", b.into()), ("c", c.into())])
+            }
+        }),
+    );
+    put(
+        "projection",
+        b.projection.map(|p| match p {
+            Projection::Perspective => "perspective".into(),
+            Projection::Orthographic { height } => {
+                typed("orthographic", vec![("height", height.into())])
+            }
+            Projection::Equirectangular => "equirectangular".into(),
+            Projection::Fisheye { fov } => {
+                typed("fisheye", vec![("fov", fov.into())])
+            }
+            Projection::CubeMap => "cube_map".into(),
+        }),
+    );
+    put(
+        "stereo",
+        b.stereo.map(|s| {
+            let mode = match s.mode {
+                StereoMode::Parallel => "parallel",
+                StereoMode::ToeIn => "toe_in",
+            };
+            let mut table = Table::new();
+            table.insert("mode".to_string(), mode.into());
+            table.insert("separation".to_string(), s.separation.into());
+            table.insert("convergence".to_string(), s.convergence.into());
+            Value::Table(table)
+        }),
+    );
+    Value::Table(t)
+}
+
+fn background_value(background: &Background) -> Value {
+    match background {
+        Background::None => "none".into(),
+        Background::Solid(color) => vec3_value(*color),
+        Background::Gradient { from, to } => {
+            let mut table = Table::new();
+            table.insert("from".to_string(), vec3_value(*from));
+            table.insert("to".to_string(), vec3_value(*to));
+            Value::Table(table)
+        }
+    }
+}
+
+// absolute form of a path relative to the working directory, with '.'
+// and '..' resolved lexically
+fn absolute(path: &Path) -> PathBuf {
+    let cwd = env::current_dir().unwrap_or_default();
+    let mut absolute = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            component => absolute.push(component),
+        }
+    }
+    absolute
+}
+
+// loaded files keep the path they were opened with, which is relative to
+// the working directory, so it is rewritten relative to dir for the saved
+// scene to find them again wherever it is written
+fn relative_path(path: &Path, dir: &Path) -> String {
+    let (path, dir) = (absolute(path), absolute(dir));
+    let common = path
+        .components()
+        .zip(dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    // paths on different drives have nothing in common
+    if common == 0 {
+        return path.display().to_string();
+    }
+    let mut relative: PathBuf =
+        dir.components().skip(common).map(|_| "..").collect();
+    relative.extend(path.components().skip(common));
+    relative.display().to_string()
+}
+
+fn texture_value(
+    texture: &Texture,
+    field: &str,
+    dir: &Path,
+) -> Result<Value, String> {
+    Ok(match texture {
+        Texture::Solid(solid) => vec3_value(solid.color),
+        Texture::Checker(c) => typed(
+            "checker",
+            vec![
+                ("scale", c.scale.into()),
+                ("even", vec3_value(c.even)),
+                ("odd", vec3_value(c.odd)),
+            ],
+        ),
+        Texture::Noise(noise) => {
+            let (kind, depth) = match noise.kind {
+                NoiseKind::Perlin => ("perlin", None),
+                NoiseKind::Turbulence { depth } => ("turbulence", Some(depth)),
+                NoiseKind::Marble { depth } => ("marble", Some(depth)),
+            };
+            let mut fields = vec![("kind", kind.into())];
+            if let Some(depth) = depth {
+                fields.push(("depth", int_value(depth)));
+            }
+            fields.push(("scale", noise.scale.into()));
+            fields.push(("color", vec3_value(noise.color)));
+            typed("noise", fields)
+        }
+        Texture::Image(image) => {
+            let Some(path) = &image.path else {
+                return Err(format!(
+                    "{field}: image texture has no source file"
+                ));
+            };
+            let path = relative_path(path, dir);
+            typed("image", vec![("path", path.into())])
+        }
+    })
+}
+
+fn material_value(material: &Material, dir: &Path) -> Result<Value, String> {
+    Ok(match material {
+        Material::Diffuse(d) => typed(
+            "diffuse",
+            vec![("albedo", texture_value(&d.albedo, "albedo", dir)?)],
+        ),
+        Material::Metal(m) => typed(
+            "metal",
+            vec![
+                ("albedo", texture_value(&m.albedo, "albedo", dir)?),
+                ("fuzz", m.fuzz.into()),
+            ],
+        ),
+        Material::Dielectric(d) => {
+            typed("dielectric", vec![("refraction", d.refraction.into())])
+        }
+        Material::Emissive(e) => typed(
+            "emissive",
+            vec![
+                ("color", texture_value(&e.color, "color", dir)?),
+                ("intensity", e.intensity.into()),
+            ],
+        ),
+    })
+}
+
+fn mesh_value(mesh: &Mesh) -> Vec<(&'static str, Value)> {
+    let list = |items: Vec<Value>| Value::Array(items);
+    let faces = mesh.faces();
+    let triples = |f: &dyn Fn(&FaceIndex) -> Option<usize>| {
+        let all: Option<Vec<Value>> = faces
+            .iter()
+            .map(|face| {
+                let idx: Option<Vec<Value>> =
+                    face.iter().map(|i| f(i).map(int_value)).collect();
+                idx.map(Value::Array)
+            })
+            .collect();
+        all.map(Value::Array)
+    };
+    let mut fields = vec![(
+        "vertices",
+        list(mesh.vertices().iter().map(|v| vec3_value(*v)).collect()),
+    )];
+    // per corner normals and uvs are only kept when every face has them
+    let face_normals = triples(&|i| i.normal);
+    let face_uvs = triples(&|i| i.uv);
+    if let Some(face_normals) = face_normals.filter(|_| !faces.is_empty()) {
+        let normals = mesh.normals().iter().map(|v| vec3_value(*v));
+        fields.push(("normals", list(normals.collect())));
+        fields.push(("face_normals", face_normals));
+    }
+    if let Some(face_uvs) = face_uvs.filter(|_| !faces.is_empty()) {
+        let uvs = mesh.uvs().iter().map(|uv| uv_value(*uv));
+        fields.push(("uvs", list(uvs.collect())));
+        fields.push(("face_uvs", face_uvs));
+    }
+    fields.push(("faces", triples(&|i| Some(i.vertex)).unwrap()));
+    fields
+}
+
+fn object_value(object: &Object, material: String) -> Value {
+    let mut fields: Vec<(&str, Value)> = match object {
+        Object::Sphere(s) => vec![
+            ("center", vec3_value(s.center)),
+            ("radius", s.radius.into()),
+        ],
+        Object::Cube(c) => vec![
+            ("corner", vec3_value(c.center)),
+            ("length", c.length.into()),
+        ],
+        Object::Quad(q) => vec![
+            ("q", vec3_value(q.q)),
+            ("u", vec3_value(q.u)),
+            ("v", vec3_value(q.v)),
+        ],
+        Object::Triangle(t) => {
+            let mut fields = vec![
+                ("a", vec3_value(t.a)),
+                ("b", vec3_value(t.b)),
+                ("c", vec3_value(t.c)),
+            ];
+            if let Some(normals) = t.normals {
+                let normals = normals.map(vec3_value).to_vec();
+                fields.push(("normals", Value::Array(normals)));
+            }
+            if let Some(uvs) = t.uvs {
+                fields.push(("uvs", Value::Array(uvs.map(uv_value).to_vec())));
+            }
+            fields
+        }
+        Object::Mesh(m) => mesh_value(m),
+    };
+    let kind = match object {
+        Object::Sphere(_) => "sphere",
+        Object::Cube(_) => "cube",
+        Object::Quad(_) => "quad",
+        Object::Triangle(_) => "triangle",
+        Object::Mesh(_) => "mesh",
+    };
+    fields.insert(0, ("material", material.into()));
+    typed(kind, fields)
+}
+
+// equal materials are written once and shared by name
+fn material_name(materials: &mut Vec<(String, Value)>, value: Value) -> String {
+    if let Some((name, _)) = materials.iter().find(|(_, v)| *v == value) {
+        return name.clone();
+    }
+    let taken = |n: &String| materials.iter().any(|(m, _)| m == n);
+    let name = (materials.len()..)
+        .map(|n| format!("material{n}"))
+        .find(|n| !taken(n))
+        .unwrap();
+    materials.push((name.clone(), value));
+    name
+}
+
+// file paths are written relative to dir
+fn serialize(
+    file: &str,
+    scene: &Scene,
+    dir: &Path,
+) -> Result<String, LoadError> {
+    let mut materials: Vec<(String, Value)> = Vec::new();
+    for (name, material) in &scene.materials {
+        let value = material_value(material, dir).map_err(|message| {
+            let field = format!("materials.{name}");
+            LoadError::field(file, &field, message)
+        })?;
+        materials.push((name.clone(), value));
+    }
+    let list = &scene.objects.objects;
+    let mut values = Vec::new();
+    for (idx, object) in list.iter().enumerate() {
+        let value =
+            material_value(object.material(), dir).map_err(|message| {
+                let field = format!("objects[{idx}].material");
+                LoadError::field(file, &field, message)
+            })?;
+        values.push(value);
+    }
+    let mut objects = Vec::new();
+    let mut idx = 0;
+    while idx < list.len() {
+        // the meshes of one obj file share their buffers, they are written
+        // back as a single reference to the file, with the material only
+        // when they all agree on it and the mtl files are otherwise used
+        let group = match &list[idx] {
+            Object::Mesh(mesh) => mesh.path.as_ref().map(|path| {
+                let same_file = |object: &&Object| match object {
+                    Object::Mesh(other) => {
+                        Arc::ptr_eq(other.vertices(), mesh.vertices())
+                    }
+                    _ => false,
+                };
+                (path, list[idx..].iter().take_while(same_file).count())
+            }),
+            _ => None,
+        };
+        let Some((path, len)) = group else {
+            let name = material_name(&mut materials, values[idx].clone());
+            objects.push(object_value(&list[idx], name));
+            idx += 1;
+            continue;
+        };
+        let shared = &values[idx..idx + len];
+        let mut fields = Vec::new();
+        if shared.iter().all(|value| *value == shared[0]) {
+            let name = material_name(&mut materials, shared[0].clone());
+            fields.push(("material", name.into()));
+        }
+        fields.push(("path", relative_path(path, dir).into()));
+        objects.push(typed("mesh", fields));
+        idx += len;
+    }
+    let mut root = Table::new();
+    root.insert("camera".to_string(), camera_value(&scene.builder));
+    root.insert(
+        "background".to_string(),
+        background_value(&scene.background),
+    );
+    root.insert(
+        "materials".to_string(),
+        Value::Table(materials.into_iter().collect()),
+    );
+    root.insert("objects".to_string(), Value::Array(objects));
+    Ok(root.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = "[camera]\nposition = [0, 1, 3]\ntarget = [0, 0, 0]\n";
+
+    fn parse(text: &str) -> Result<Scene, LoadError> {
+        parse_scene("test.toml", &format!("{CAMERA}{text}"), Path::new(""))
+    }
+
+    fn error(text: &str) -> String {
+        match parse(text) {
+            Ok(_) => panic!("scene should not load"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn errors_name_the_offending_field() {
+        let materials =
+            "[materials.red]\ntype = \"diffuse\"\nalbedo = [1, 0, 0]\n";
+        let sphere = "[[objects]]\ntype = \"sphere\"\nmaterial = \"red\"\n\
+                      center = [0, 0, 0]\nradius = 1\n";
+        let text = format!(
+            "{materials}{sphere}{sphere}[[objects]]\ntype = \"sphere\"\n\
+             material = \"blue\"\ncenter = [0, 0, 0]\nradius = 1\n"
+        );
+        assert_eq!(
+            error(&text),
+            "test.toml: objects[2].material: unknown material 'blue'"
+        );
+        let text = sphere.replace("radius = 1", "radius = \"1\"");
+        assert_eq!(
+            error(&format!("{materials}{text}")),
+            "test.toml: objects[0].radius: expected a number, found string"
+        );
+        assert_eq!(
+            error("[materials.red]\ntype = \"diffuse\"\nalbdo = [1, 0, 0]\n"),
+            "test.toml: materials.red.albdo: unknown field, expected type, \
+             albedo"
+        );
+        assert_eq!(
+            error("[materials.red]\ntype = \"glass\"\n"),
+            "test.toml: materials.red.type: unknown type 'glass', expected \
+             diffuse, metal, dielectric or emissive"
+        );
+    }
+
+    #[test]
+    fn saved_scenes_find_their_files_again() {
+        let root =
+            env::temp_dir().join(format!("lumen-scene-{}", std::process::id()));
+        let (scenes, elsewhere) = (root.join("scenes"), root.join("elsewhere"));
+        fs::create_dir_all(&scenes).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        fs::write(scenes.join("wood.ppm"), "P3 1 1 255 200 100 50\n").unwrap();
+        fs::write(scenes.join("model.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        let obj = "mtllib model.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\n\
+                   usemtl red\nf 1 2 3\ng back\nf 1 3 4\n";
+        fs::write(scenes.join("model.obj"), obj).unwrap();
+        let text = format!(
+            "{CAMERA}[materials.wood]\ntype = \"diffuse\"\n\
+             albedo = {{ type = \"image\", path = \"wood.ppm\" }}\n\
+             [[objects]]\ntype = \"mesh\"\npath = \"model.obj\"\n\
+             [[objects]]\ntype = \"sphere\"\nmaterial = \"wood\"\n\
+             center = [0, 0, 0]\nradius = 1\n"
+        );
+        fs::write(scenes.join("scene.toml"), text).unwrap();
+
+        let scene = load_scene(scenes.join("scene.toml")).unwrap();
+        let beside = scenes.join("copy.toml");
+        let away = elsewhere.join("copy.toml");
+        save_scene(&beside, &scene).unwrap();
+        save_scene(&away, &scene).unwrap();
+        let beside_text = fs::read_to_string(&beside).unwrap();
+        let away_text = fs::read_to_string(&away).unwrap();
+        let reloaded = [load_scene(&beside), load_scene(&away)];
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(beside_text.contains("path = \"wood.ppm\""));
+        assert!(beside_text.contains("path = \"model.obj\""));
+        assert!(away_text.contains("path = \"../scenes/wood.ppm\""));
+        assert!(away_text.contains("path = \"../scenes/model.obj\""));
+        for copy in reloaded {
+            let copy = copy.unwrap();
+            assert_eq!(copy.objects.objects.len(), 3);
+            assert_eq!(copy.to_toml().unwrap(), scene.to_toml().unwrap());
+        }
+    }
+}
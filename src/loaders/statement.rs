@@ -0,0 +1,85 @@
+use crate::math::Vec3;
+
+use super::LoadError;
+
+// a single non-empty line of an obj or mtl file split into a keyword and
+// its whitespace separated arguments
+pub struct Statement<'a> {
+    pub file: &'a str,
+    pub line: usize,
+    pub keyword: &'a str,
+    pub args: Vec<&'a str>,
+}
+
+impl<'a> Statement<'a> {
+    pub fn parse_all(
+        file: &'a str,
+        text: &'a str,
+    ) -> impl Iterator<Item = Statement<'a>> {
+        text.lines().enumerate().filter_map(move |(idx, raw)| {
+            let content = match raw.find('#') {
+                Some(pos) => &raw[..pos],
+                None => raw,
+            };
+            let mut tokens = content.split_whitespace();
+            let keyword = tokens.next()?;
+            Some(Statement {
+                file,
+                line: idx + 1,
+                keyword,
+                args: tokens.collect(),
+            })
+        })
+    }
+
+    pub fn error(&self, message: String) -> LoadError {
+        LoadError::parse(self.file, self.line, message)
+    }
+
+    // the remainder of the line, for names that may contain spaces
+    pub fn name(&self) -> Result<String, LoadError> {
+        match self.args.is_empty() {
+            true => Err(self.error(format!("'{}' needs a name", self.keyword))),
+            false => Ok(self.args.join(" ")),
+        }
+    }
+
+    pub fn floats(
+        &self,
+        min: usize,
+        max: usize,
+    ) -> Result<Vec<f64>, LoadError> {
+        if self.args.len() < min || self.args.len() > max {
+            let expected = match min == max {
+                true => format!("{}", min),
+                false => format!("{} to {}", min, max),
+            };
+            return Err(self.error(format!(
+                "'{}' expects {} numbers, found {}",
+                self.keyword,
+                expected,
+                self.args.len()
+            )));
+        }
+        self.args
+            .iter()
+            .map(|arg| {
+                arg.parse::<f64>().map_err(|_| {
+                    self.error(format!(
+                        "invalid number '{}' in '{}'",
+                        arg, self.keyword
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    pub fn float(&self) -> Result<f64, LoadError> {
+        Ok(self.floats(1, 1)?[0])
+    }
+
+    pub fn vec3(&self) -> Result<Vec3, LoadError> {
+        let v = self.floats(3, 3)?;
+        Ok(Vec3::new(v[0], v[1], v[2]))
+    }
+}
@@ -1,47 +1,354 @@
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io;
+use std::io::{BufWriter, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 
+use lumen::loaders::{load_scene, LoadError, Scene};
+use lumen::objects::{LightList, Object, ObjectBvh, Physical};
 use lumen::rendering::*;
-use lumen::Material;
-use lumen::ObjectList;
+use lumen::Progress;
 
-fn make_cube_array() -> ObjectList {
-    let mut objects = ObjectList::new();
-    for i in 0..4 {
-        for j in 0..4 {
-            let (cx, cy) = (3.0 * i as f64, 3.0 * j as f64);
-            let mat = Material::new_metal(0.5, 0.5, 0.7, 0.0);
-            objects.add_cube(1.0, cx, 0.5, cy, mat);
+const USAGE: &str = "\
+usage: lumen render <scene> [options]
+       lumen info <scene>
+
+options for render:
+  -o, --output <path>        image to write, defaults to the scene name
+                             with a png extension
+      --resolution <w>x<h>   override the camera resolution
+      --samples <n>          samples per pixel
+      --max-depth <n>        maximum bounces per path
+      --threads <n>          worker threads, defaults to the number of
+                             available cores
+      --seed <n>             seed for a reproducible render
+      --format <format>      ppm, png, png16, pfm, hdr, exr or exr-float,
+                             taken from the output extension by default";
+
+enum Command {
+    Render(RenderArgs),
+    Info(PathBuf),
+    Help,
+}
+
+#[derive(Default)]
+struct RenderArgs {
+    scene: PathBuf,
+    output: Option<PathBuf>,
+    resolution: Option<(usize, usize)>,
+    samples: Option<usize>,
+    max_depth: Option<usize>,
+    threads: Option<usize>,
+    seed: Option<u64>,
+    format: Option<ImageFormat>,
+}
+
+enum CliError {
+    // bad command line, reported along with the usage text
+    Usage(String),
+    Load(LoadError),
+    Render(RenderError),
+    Io(PathBuf, io::Error),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(message) => write!(f, "{message}"),
+            Self::Load(err) => write!(f, "{err}"),
+            Self::Render(err) => {
+                let (row, col) = err.input;
+                let message = &err.message;
+                write!(
+                    f,
+                    "pixel at row {row}, column {col} panicked: {message}"
+                )
+            }
+            Self::Io(path, err) => write!(f, "{}: {err}", path.display()),
         }
     }
-    let ground = Material::new_diffuse(0.5, 0.5, 0.5);
-    objects.add_sphere(1000.0, 0.0, -1000.0, 0.0, ground);
-    objects
 }
 
-fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let output_path = PathBuf::from(args[1].as_str());
-    let mut output = File::create(output_path)?;
+impl From<LoadError> for CliError {
+    fn from(err: LoadError) -> Self {
+        Self::Load(err)
+    }
+}
+
+impl From<RenderError> for CliError {
+    fn from(err: RenderError) -> Self {
+        Self::Render(err)
+    }
+}
 
-    let objects = make_cube_array();
-    let camera = CameraBuilder::new()
-        .resolution(1024, 576)
-        .vfov(90.0)
-        .target(6.0, 0.0, 6.0)
-        .position(8.0, 8.0, 8.0)
-        .upward(0.0, 1.0, 0.0)
-        .samples(100)
-        .max_depth(10)
-        .build();
+fn usage(message: impl Into<String>) -> CliError {
+    CliError::Usage(message.into())
+}
+
+fn number<T: std::str::FromStr>(
+    flag: &str,
+    value: &str,
+) -> Result<T, CliError> {
+    value
+        .parse()
+        .map_err(|_| usage(format!("{flag} expects a number, found '{value}'")))
+}
 
-    let renderer = Renderer::new(camera, objects);
-    let image = renderer.render();
+fn positive(flag: &str, value: &str) -> Result<usize, CliError> {
+    match number(flag, value)? {
+        0 => Err(usage(format!("{flag} must be at least 1"))),
+        n => Ok(n),
+    }
+}
+
+fn resolution(value: &str) -> Result<(usize, usize), CliError> {
+    let invalid =
+        || usage(format!("--resolution expects <w>x<h>, found '{value}'"));
+    let (w, h) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    match (w.parse(), h.parse()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_render(args: &[String]) -> Result<RenderArgs, CliError> {
+    let mut parsed = RenderArgs::default();
+    let mut scene = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            match scene {
+                None => scene = Some(PathBuf::from(arg)),
+                Some(_) => {
+                    return Err(usage(format!("unexpected argument '{arg}'")))
+                }
+            }
+            continue;
+        }
+        // both --flag value and --flag=value are accepted
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let mut value = || match inline.clone().or_else(|| iter.next().cloned())
+        {
+            Some(value) => Ok(value),
+            None => Err(usage(format!("{flag} expects a value"))),
+        };
+        match flag {
+            "-o" | "--output" => parsed.output = Some(PathBuf::from(value()?)),
+            "--resolution" => parsed.resolution = Some(resolution(&value()?)?),
+            "--samples" => parsed.samples = Some(positive(flag, &value()?)?),
+            "--max-depth" => {
+                parsed.max_depth = Some(positive(flag, &value()?)?)
+            }
+            "--threads" => parsed.threads = Some(positive(flag, &value()?)?),
+            "--seed" => parsed.seed = Some(number(flag, &value()?)?),
+            "--format" => {
+                let name = value()?;
+                let format =
+                    ImageFormat::from_name(&name).ok_or_else(|| {
+                        usage(format!("unknown image format '{name}'"))
+                    })?;
+                parsed.format = Some(format);
+            }
+            _ => return Err(usage(format!("unknown option '{flag}'"))),
+        }
+    }
+    parsed.scene = scene.ok_or_else(|| usage("render expects a scene file"))?;
+    Ok(parsed)
+}
+
+fn parse(args: &[String]) -> Result<Command, CliError> {
+    let Some((command, rest)) = args.split_first() else {
+        return Err(usage("missing command"));
+    };
+    match command.as_str() {
+        "render" => Ok(Command::Render(parse_render(rest)?)),
+        "info" => match rest {
+            [scene] if !scene.starts_with('-') => {
+                Ok(Command::Info(PathBuf::from(scene)))
+            }
+            [] => Err(usage("info expects a scene file")),
+            _ => Err(usage("info takes only a scene file")),
+        },
+        "help" | "-h" | "--help" => Ok(Command::Help),
+        _ => Err(usage(format!("unknown command '{command}'"))),
+    }
+}
+
+fn render(args: RenderArgs) -> Result<(), CliError> {
+    let mut scene = load_scene(&args.scene)?;
+    let builder = &mut scene.builder;
+    if let Some((w, h)) = args.resolution {
+        builder.resolution(w, h);
+    }
+    if let Some(samples) = args.samples {
+        builder.samples(samples);
+    }
+    if let Some(depth) = args.max_depth {
+        builder.max_depth(depth);
+    }
+    // overrides can still make the camera invalid, e.g. a zero resolution
+    scene.camera = builder.build().map_err(|err| {
+        let file = args.scene.display().to_string();
+        LoadError::field(&file, "camera", err.to_string())
+    })?;
 
-    let output_text = format!("{image}");
-    output.write_all(output_text.as_bytes())?;
+    let output = args
+        .output
+        .unwrap_or_else(|| args.scene.with_extension("png"));
+    let format = match args.format {
+        Some(format) => format,
+        None => ImageFormat::from_path(&output).ok_or_else(|| {
+            usage(format!(
+                "cannot tell the format of '{}', pass --format",
+                output.display()
+            ))
+        })?,
+    };
 
+    let stereo = scene.camera.stereo.is_some();
+    let mut renderer = Renderer::new(scene.camera, scene.objects);
+    renderer.background(scene.background);
+    if let Some(seed) = args.seed {
+        renderer.seed(seed);
+    }
+    if let Some(threads) = args.threads {
+        renderer.threads(threads);
+    }
+    // keep redirected output free of carriage return noise
+    if io::stderr().is_terminal() {
+        renderer.on_progress(draw_progress);
+    }
+    // stereo rigs are written as one side by side image
+    let image = match stereo {
+        true => renderer.render_stereo_composite(StereoLayout::SideBySide)?,
+        false => renderer.render()?,
+    };
+
+    let io_error = |err| CliError::Io(output.clone(), err);
+    let file = File::create(&output).map_err(io_error)?;
+    let transform = OutputTransform::default();
+    write_image(BufWriter::new(file), &image, format, &transform)
+        .map_err(io_error)
+}
+
+fn clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+// redraws a single status line on stderr, ending it once the image is done
+fn draw_progress(progress: &Progress) {
+    const WIDTH: usize = 30;
+    let filled = ((progress.fraction() * WIDTH as f64) as usize).min(WIDTH);
+    let bar = format!("{}{}", "#".repeat(filled), "-".repeat(WIDTH - filled));
+    let percent = 100.0 * progress.fraction();
+    let rate = progress.throughput() / 1e3;
+    match progress.is_done() {
+        true => {
+            let elapsed = clock(progress.elapsed);
+            eprintln!(
+                "\r[{bar}] {percent:3.0}% {rate:.1} kpx/s in {elapsed}  "
+            );
+        }
+        false => {
+            let eta = progress.eta().map_or("?".to_string(), clock);
+            eprint!("\r[{bar}] {percent:3.0}% {rate:.1} kpx/s eta {eta}  ");
+        }
+    }
+}
+
+fn object_kind(object: &Object) -> &'static str {
+    match object {
+        Object::Sphere(_) => "sphere",
+        Object::Cube(_) => "cube",
+        Object::Quad(_) => "quad",
+        Object::Triangle(_) => "triangle",
+        Object::Mesh(_) => "mesh",
+    }
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    match (n, noun.ends_with('h')) {
+        (1, _) => format!("{n} {noun}"),
+        (_, true) => format!("{n} {noun}es"),
+        (_, false) => format!("{n} {noun}s"),
+    }
+}
+
+fn info(path: &Path) -> Result<(), CliError> {
+    let Scene {
+        objects,
+        camera,
+        materials,
+        ..
+    } = load_scene(path)?;
+    let mut kinds = BTreeMap::new();
+    let mut faces = 0;
+    for object in &objects.objects {
+        *kinds.entry(object_kind(object)).or_insert(0) += 1;
+        if let Object::Mesh(mesh) = object {
+            faces += mesh.len();
+        }
+    }
+    let kinds: Vec<String> = kinds.iter().map(|(k, n)| plural(*n, k)).collect();
+    let lights = LightList::new(&objects).len();
+    let bounds = objects.bounding_box();
+    let count = objects.objects.len();
+
+    println!("scene: {}", path.display());
+    match kinds.is_empty() {
+        true => println!("objects: 0"),
+        false => println!("objects: {count} ({})", kinds.join(", ")),
+    }
+    if faces > 0 {
+        println!("mesh faces: {faces}");
+    }
+    println!("materials: {}", materials.len());
+    println!("lights: {lights}");
+    match bounds.is_empty() {
+        true => println!("bounds: empty"),
+        false => {
+            println!("bounds: {} to {}", bounds.min, bounds.max);
+            println!("extent: {}", bounds.extent());
+        }
+    }
+    println!(
+        "camera: {}x{}, {} samples, max depth {}",
+        camera.image_width,
+        camera.image_height,
+        camera.samples,
+        camera.max_depth
+    );
+    println!("bvh: {}", ObjectBvh::new(objects).stats());
     Ok(())
 }
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = parse(&args).and_then(|command| match command {
+        Command::Render(args) => render(args),
+        Command::Info(path) => info(&path),
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err @ CliError::Usage(_)) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
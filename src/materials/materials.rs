@@ -1,21 +1,15 @@
 use crate::math::*;
 use crate::objects::HitRecord;
-use rand::distributions::{Distribution, Uniform};
+use crate::textures::{Texture, TextureValue};
 
-pub fn random_double() -> f64 {
-    let dist = Uniform::new(0.0, 1.0);
-    let mut rng = rand::thread_rng();
-    dist.sample(&mut rng)
-}
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Diffuse {
-    pub albedo: Vec3,
+    pub albedo: Texture,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Metal {
-    pub albedo: Vec3,
+    pub albedo: Texture,
     pub fuzz: f64,
 }
 
@@ -24,28 +18,42 @@ pub struct Dielectric {
     pub refraction: f64,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
+pub struct Emissive {
+    pub color: Texture,
+    pub intensity: f64,
+}
+
+#[derive(Debug, Clone)]
 pub enum Material {
     Diffuse(Diffuse),
     Metal(Metal),
     Dielectric(Dielectric),
+    Emissive(Emissive),
 }
 
 impl Material {
     pub fn new_diffuse(r: f64, g: f64, b: f64) -> Self {
-        Self::Diffuse(Diffuse {
-            albedo: Vec3::new(r, g, b),
-        })
+        Self::new_diffuse_texture(Texture::new_solid(r, g, b))
+    }
+    pub fn new_diffuse_texture(albedo: Texture) -> Self {
+        Self::Diffuse(Diffuse { albedo })
     }
     pub fn new_metal(r: f64, g: f64, b: f64, fuzz: f64) -> Self {
-        Self::Metal(Metal {
-            albedo: Vec3::new(r, g, b),
-            fuzz,
-        })
+        Self::new_metal_texture(Texture::new_solid(r, g, b), fuzz)
+    }
+    pub fn new_metal_texture(albedo: Texture, fuzz: f64) -> Self {
+        Self::Metal(Metal { albedo, fuzz })
     }
     pub fn new_dielectric(refraction: f64) -> Self {
         Self::Dielectric(Dielectric { refraction })
     }
+    pub fn new_emissive(r: f64, g: f64, b: f64, intensity: f64) -> Self {
+        Self::new_emissive_texture(Texture::new_solid(r, g, b), intensity)
+    }
+    pub fn new_emissive_texture(color: Texture, intensity: f64) -> Self {
+        Self::Emissive(Emissive { color, intensity })
+    }
 }
 
 pub trait Scatter {
@@ -56,6 +64,21 @@ pub trait Scatter {
         attenuation: &mut Vec3,
         scattered: &mut Ray,
     ) -> bool;
+
+    fn emitted(&self, _record: &HitRecord) -> Vec3 {
+        Vec3::default()
+    }
+
+    // solid angle density of scattering toward `scattered`, zero for
+    // specular materials which cannot be sampled toward a light
+    fn scattering_pdf(
+        &self,
+        _r: &Ray,
+        _record: &HitRecord,
+        _scattered: &Ray,
+    ) -> f64 {
+        0.0
+    }
 }
 
 impl Scatter for Diffuse {
@@ -66,13 +89,23 @@ impl Scatter for Diffuse {
         attenuation: &mut Vec3,
         scattered: &mut Ray,
     ) -> bool {
-        let mut scatter_direction = record.normal + Vec3::random_vector();
+        let mut scatter_direction = record.normal + Vec3::random_unit_vector();
         if scatter_direction.near_zero() {
             scatter_direction = record.normal
         }
         *scattered = Ray::new(record.point, scatter_direction);
-        *attenuation = self.albedo.clone();
-        return true;
+        *attenuation = self.albedo.value(record.u, record.v, record.point);
+        true
+    }
+
+    fn scattering_pdf(
+        &self,
+        _r: &Ray,
+        record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        let cosine = record.normal * Vec3::unit_vector(scattered.direction);
+        cosine.max(0.0) / std::f64::consts::PI
     }
 }
 
@@ -85,11 +118,11 @@ impl Scatter for Metal {
         scattered: &mut Ray,
     ) -> bool {
         let mut reflected = Vec3::reflect(r.direction, record.normal);
-        reflected =
-            Vec3::unit_vector(reflected) + self.fuzz * Vec3::random_vector();
+        reflected = Vec3::unit_vector(reflected)
+            + self.fuzz * Vec3::random_unit_vector();
         *scattered = Ray::new(record.point, reflected);
-        *attenuation = self.albedo.clone();
-        return (scattered.direction * record.normal) > 0.0;
+        *attenuation = self.albedo.value(record.u, record.v, record.point);
+        (scattered.direction * record.normal) > 0.0
     }
 }
 
@@ -119,7 +152,25 @@ impl Scatter for Dielectric {
             false => Vec3::refract(unit_direction, record.normal, ri),
         };
         *scattered = Ray::new(record.point, direction);
-        return true;
+        true
+    }
+}
+
+// light sources absorb every incoming ray and only contribute emission
+impl Scatter for Emissive {
+    fn scatter(
+        &self,
+        _r: &Ray,
+        _record: &HitRecord,
+        _attenuation: &mut Vec3,
+        _scattered: &mut Ray,
+    ) -> bool {
+        false
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Vec3 {
+        let (u, v, p) = (record.u, record.v, record.point);
+        self.color.value(u, v, p) * self.intensity
     }
 }
 
@@ -139,6 +190,28 @@ impl Scatter for Material {
             Self::Dielectric(mat) => {
                 mat.scatter(r, record, attenuation, scattered)
             }
+            Self::Emissive(mat) => {
+                mat.scatter(r, record, attenuation, scattered)
+            }
+        }
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Vec3 {
+        match self {
+            Self::Emissive(mat) => mat.emitted(record),
+            _ => Vec3::default(),
+        }
+    }
+
+    fn scattering_pdf(
+        &self,
+        r: &Ray,
+        record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        match self {
+            Self::Diffuse(mat) => mat.scattering_pdf(r, record, scattered),
+            _ => 0.0,
         }
     }
 }
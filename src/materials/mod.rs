@@ -1,4 +1,5 @@
 // modules
+#[allow(clippy::module_inception)]
 mod materials;
 
 // flatten
@@ -0,0 +1,117 @@
+use super::{Interval, Ray, Vec3};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    // the identity for union, contains nothing
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vec3::new(
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ),
+        }
+    }
+
+    pub fn from_points(points: &[Vec3]) -> Self {
+        points
+            .iter()
+            .fold(Self::empty(), |acc, p| acc.union(&Self::new(*p, *p)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x
+            || self.min.y > self.max.y
+            || self.min.z > self.max.z
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: Vec3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vec3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    // widens flat boxes (e.g. around an axis aligned quad) so that the
+    // slab test never works with a zero width interval
+    pub fn padded(&self, delta: f64) -> Self {
+        let pad = |min: f64, max: f64| match max - min < delta {
+            true => (min - delta / 2.0, max + delta / 2.0),
+            false => (min, max),
+        };
+        let (x0, x1) = pad(self.min.x, self.max.x);
+        let (y0, y1) = pad(self.min.y, self.max.y);
+        let (z0, z1) = pad(self.min.z, self.max.z);
+        Self::new(Vec3::new(x0, y0, z0), Vec3::new(x1, y1, z1))
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.extent();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let d = self.extent();
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, rt: &Interval) -> bool {
+        let (mut t_min, mut t_max) = (rt.min, rt.max);
+        for axis in 0..3 {
+            let inv = 1.0 / r.direction.axis(axis);
+            let origin = r.origin.axis(axis);
+            let mut t0 = (self.min.axis(axis) - origin) * inv;
+            let mut t1 = (self.max.axis(axis) - origin) * inv;
+            if inv < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -1,10 +1,15 @@
 // modules
+pub mod aabb;
 pub mod ray;
 pub mod util;
 pub mod vec3;
 
 // flatten
+pub use aabb::Aabb;
 pub use ray::Interval;
 pub use ray::Ray;
 pub use util::lerp;
+pub use util::power_heuristic;
+pub use util::random_double;
+pub use util::seed_rng;
 pub use vec3::Vec3;
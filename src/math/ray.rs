@@ -1,6 +1,6 @@
 use super::Vec3;
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
@@ -11,13 +11,6 @@ impl Ray {
         Ray { origin, direction }
     }
 
-    pub fn default() -> Self {
-        Self {
-            origin: Vec3::default(),
-            direction: Vec3::default(),
-        }
-    }
-
     pub fn at(&self, t: f64) -> Vec3 {
         self.origin + self.direction * t
     }
@@ -30,7 +23,7 @@ pub struct Interval {
 
 impl Interval {
     pub fn new(min: f64, max: f64) -> Self {
-        Interval { min: min, max: max }
+        Interval { min, max }
     }
 
     #[allow(dead_code)]
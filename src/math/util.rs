@@ -1,7 +1,40 @@
+use std::cell::RefCell;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
 use super::*;
 
+thread_local! {
+    // every sample on a thread draws from here, so seeding it makes a
+    // sequence of draws reproducible
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+pub fn random_double() -> f64 {
+    with_rng(|rng| rng.gen())
+}
+
 pub fn lerp(r: &Ray, color_from: Vec3, color_to: Vec3) -> Vec3 {
     let unit_direction = Vec3::unit_vector(r.direction);
     let a = 0.5 * (unit_direction.y + 1.0);
-    return (1.0 - a) * color_from + a * color_to;
+    (1.0 - a) * color_from + a * color_to
+}
+
+// multiple importance sampling weight for a sample drawn from the
+// strategy with density f, when g is the density of the other strategy
+pub fn power_heuristic(f: f64, g: f64) -> f64 {
+    let (f2, g2) = (f * f, g * g);
+    match f2 + g2 > 0.0 {
+        true => f2 / (f2 + g2),
+        false => 0.0,
+    }
 }
@@ -5,6 +5,8 @@ use std::ops::{
 
 use rand::distributions::{Distribution, Uniform};
 
+use super::util::with_rng;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vec3 {
     pub x: f64,
@@ -12,17 +14,19 @@ pub struct Vec3 {
     pub z: f64,
 }
 
-impl Vec3 {
-    pub fn default() -> Self {
+impl Default for Vec3 {
+    fn default() -> Self {
         Vec3 {
             x: 0.0,
             y: 0.0,
             z: 0.0,
         }
     }
+}
 
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
-        Vec3 { x: x, y: y, z: z }
+impl Vec3 {
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Vec3 { x, y, z }
     }
 
     pub fn unit_vector(v: Vec3) -> Self {
@@ -52,26 +56,71 @@ impl Vec3 {
 
     pub fn random_vector() -> Self {
         let dist = Uniform::new(0.0, 1.0);
-        let mut rng = rand::thread_rng();
-        Vec3 {
-            x: dist.sample(&mut rng),
-            y: dist.sample(&mut rng),
-            z: dist.sample(&mut rng),
-        }
+        with_rng(|rng| Vec3 {
+            x: dist.sample(rng),
+            y: dist.sample(rng),
+            z: dist.sample(rng),
+        })
+    }
+
+    pub fn random_unit_vector() -> Self {
+        let dist = Uniform::new(-1.0, 1.0);
+        with_rng(|rng| loop {
+            let v = Vec3 {
+                x: dist.sample(rng),
+                y: dist.sample(rng),
+                z: dist.sample(rng),
+            };
+            let len_squared = v.len_squared();
+            if 1e-160 < len_squared && len_squared <= 1.0 {
+                return v / len_squared.sqrt();
+            }
+        })
+    }
+
+    // uniform point in the unit disk on the xy plane
+    pub fn random_in_unit_disk() -> Self {
+        let dist = Uniform::new(-1.0, 1.0);
+        with_rng(|rng| loop {
+            let v = Vec3 {
+                x: dist.sample(rng),
+                y: dist.sample(rng),
+                z: 0.0,
+            };
+            if v.len_squared() < 1.0 {
+                return v;
+            }
+        })
     }
 
     pub fn random_on_hemisphere(normal: Vec3) -> Self {
-        let on_unit_sphere = Self::random_vector();
+        let on_unit_sphere = Self::random_unit_vector();
         match on_unit_sphere * normal > 0.0 {
             true => on_unit_sphere,
             false => -1.0 * on_unit_sphere,
         }
     }
 
+    pub fn hadamard(u: Vec3, v: Vec3) -> Vec3 {
+        Vec3 {
+            x: u.x * v.x,
+            y: u.y * v.y,
+            z: u.z * v.z,
+        }
+    }
+
     pub fn reflect(v: Vec3, u: Vec3) -> Vec3 {
         v - (2.0 * v * u * u)
     }
 
+    pub fn axis(&self, axis: usize) -> f64 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
     pub fn len_squared(&self) -> f64 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
@@ -90,10 +139,7 @@ impl Vec3 {
 
     pub fn near_zero(&self) -> bool {
         let s = 0.00000001;
-        match ((self.x.abs() < s), (self.y.abs() < s), (self.z.abs() < s)) {
-            (true, true, true) => true,
-            _ => false,
-        }
+        self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
     }
 }
 
@@ -0,0 +1,419 @@
+use std::fmt;
+
+use crate::math::*;
+
+use super::HitRecord;
+use super::ObjectList;
+use super::Physical;
+
+const BIN_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+
+#[derive(Debug, Clone)]
+enum BvhNode {
+    Interior {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+        axis: usize,
+    },
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        count: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Self::Interior { bounds, .. } => bounds,
+            Self::Leaf { bounds, .. } => bounds,
+        }
+    }
+}
+
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BvhStats {
+    pub primitives: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    pub depth: usize,
+    pub min_leaf_size: usize,
+    pub max_leaf_size: usize,
+    pub mean_leaf_size: f64,
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} primitives, {} nodes, {} leaves, depth {}, \
+             leaf size {}..{} (mean {:.2})",
+            self.primitives,
+            self.nodes,
+            self.leaves,
+            self.depth,
+            self.min_leaf_size,
+            self.max_leaf_size,
+            self.mean_leaf_size
+        )
+    }
+}
+
+// binned surface area heuristic hierarchy over primitive indexes, the
+// primitives themselves are owned by the caller and intersected through
+// a callback during traversal
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    order: Vec<usize>,
+    depth: usize,
+}
+
+impl Bvh {
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut items: Vec<BuildItem> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, b)| BuildItem {
+                index,
+                bounds: *b,
+                centroid: b.centroid(),
+            })
+            .collect();
+        let mut nodes = Vec::with_capacity(2 * items.len());
+        let mut depth = 0;
+        if !items.is_empty() {
+            Self::build(&mut nodes, &mut items, 0, 1, &mut depth);
+        }
+        Self {
+            nodes,
+            order: items.iter().map(|item| item.index).collect(),
+            depth,
+        }
+    }
+
+    fn build(
+        nodes: &mut Vec<BvhNode>,
+        items: &mut [BuildItem],
+        start: usize,
+        level: usize,
+        depth: &mut usize,
+    ) -> usize {
+        *depth = (*depth).max(level);
+        let bounds = items
+            .iter()
+            .fold(Aabb::empty(), |acc, item| acc.union(&item.bounds));
+        let count = items.len();
+        let node_idx = nodes.len();
+        nodes.push(BvhNode::Leaf {
+            bounds,
+            start,
+            count,
+        });
+        if count == 1 {
+            return node_idx;
+        }
+        let Some((axis, mid)) = Self::split(items, &bounds) else {
+            return node_idx;
+        };
+        let (left_items, right_items) = items.split_at_mut(mid);
+        let left = Self::build(nodes, left_items, start, level + 1, depth);
+        let right =
+            Self::build(nodes, right_items, start + mid, level + 1, depth);
+        nodes[node_idx] = BvhNode::Interior {
+            bounds,
+            left,
+            right,
+            axis,
+        };
+        node_idx
+    }
+
+    // partitions items along the cheapest binned split and returns the
+    // split axis and index, or None when a leaf is cheaper
+    fn split(items: &mut [BuildItem], bounds: &Aabb) -> Option<(usize, usize)> {
+        let count = items.len();
+        let centroids = items.iter().fold(Aabb::empty(), |acc, i| {
+            acc.union(&Aabb::new(i.centroid, i.centroid))
+        });
+        let parent_area = bounds.surface_area();
+        let bin_of = |c: &Vec3, axis: usize| {
+            let (min, extent) =
+                (centroids.min.axis(axis), centroids.extent().axis(axis));
+            let b = ((c.axis(axis) - min) / extent * BIN_COUNT as f64) as usize;
+            b.min(BIN_COUNT - 1)
+        };
+        let mut best: Option<(f64, usize, usize)> = None;
+        for axis in 0..3 {
+            if centroids.extent().axis(axis) <= 0.0 || parent_area <= 0.0 {
+                continue;
+            }
+            let mut bins = [(Aabb::empty(), 0usize); BIN_COUNT];
+            for item in items.iter() {
+                let b = bin_of(&item.centroid, axis);
+                bins[b].0 = bins[b].0.union(&item.bounds);
+                bins[b].1 += 1;
+            }
+            // right_area[i], right_count[i] cover bins i..BIN_COUNT
+            let mut right_area = [0.0; BIN_COUNT];
+            let mut right_count = [0; BIN_COUNT];
+            let (mut acc, mut n) = (Aabb::empty(), 0);
+            for i in (1..BIN_COUNT).rev() {
+                acc = acc.union(&bins[i].0);
+                n += bins[i].1;
+                right_area[i] = acc.surface_area();
+                right_count[i] = n;
+            }
+            let (mut acc, mut n) = (Aabb::empty(), 0);
+            for i in 1..BIN_COUNT {
+                acc = acc.union(&bins[i - 1].0);
+                n += bins[i - 1].1;
+                if n == 0 || right_count[i] == 0 {
+                    continue;
+                }
+                let cost = TRAVERSAL_COST
+                    + INTERSECTION_COST
+                        * (acc.surface_area() * n as f64
+                            + right_area[i] * right_count[i] as f64)
+                        / parent_area;
+                if best.is_none_or(|(c, _, _)| cost < c) {
+                    best = Some((cost, axis, i));
+                }
+            }
+        }
+        let leaf_cost = INTERSECTION_COST * count as f64;
+        match best {
+            Some((cost, axis, split_bin))
+                if cost < leaf_cost || count > MAX_LEAF_SIZE =>
+            {
+                let mut mid = 0;
+                for i in 0..count {
+                    if bin_of(&items[i].centroid, axis) < split_bin {
+                        items.swap(i, mid);
+                        mid += 1;
+                    }
+                }
+                Some((axis, mid))
+            }
+            // either a leaf is cheaper or every centroid coincides
+            _ => None,
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(node) => *node.bounds(),
+            None => Aabb::empty(),
+        }
+    }
+
+    pub fn hit<'a, F>(
+        &self,
+        r: &Ray,
+        rt: &Interval,
+        record: &mut HitRecord<'a>,
+        mut hit_item: F,
+    ) -> bool
+    where
+        F: FnMut(usize, &Ray, &Interval, &mut HitRecord<'a>) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let mut closest = Interval::new(rt.min, rt.max);
+        let mut tmp = HitRecord::default();
+        let mut hit = false;
+        let mut stack = Vec::with_capacity(self.depth + 1);
+        stack.push(0);
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            if !node.bounds().hit(r, &closest) {
+                continue;
+            }
+            match *node {
+                BvhNode::Leaf { start, count, .. } => {
+                    for &item in &self.order[start..start + count] {
+                        if hit_item(item, r, &closest, &mut tmp) {
+                            hit = true;
+                            closest.max = tmp.t;
+                            *record = tmp;
+                        }
+                    }
+                }
+                // the nearer child is pushed last so it is visited first
+                BvhNode::Interior {
+                    left, right, axis, ..
+                } => match r.direction.axis(axis) < 0.0 {
+                    true => stack.extend([left, right]),
+                    false => stack.extend([right, left]),
+                },
+            }
+        }
+        hit
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let leaf_sizes: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter_map(|node| match node {
+                BvhNode::Leaf { count, .. } => Some(*count),
+                BvhNode::Interior { .. } => None,
+            })
+            .collect();
+        let leaves = leaf_sizes.len();
+        BvhStats {
+            primitives: self.order.len(),
+            nodes: self.nodes.len(),
+            leaves,
+            depth: self.depth,
+            min_leaf_size: leaf_sizes.iter().copied().min().unwrap_or(0),
+            max_leaf_size: leaf_sizes.iter().copied().max().unwrap_or(0),
+            mean_leaf_size: match leaves {
+                0 => 0.0,
+                n => self.order.len() as f64 / n as f64,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectBvh {
+    objects: ObjectList,
+    bvh: Bvh,
+}
+
+impl ObjectBvh {
+    pub fn new(objects: ObjectList) -> Self {
+        let bounds: Vec<Aabb> =
+            objects.objects.iter().map(|o| o.bounding_box()).collect();
+        let bvh = Bvh::new(&bounds);
+        Self { objects, bvh }
+    }
+
+    pub fn objects(&self) -> &ObjectList {
+        &self.objects
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        self.bvh.stats()
+    }
+}
+
+impl Physical for ObjectBvh {
+    fn hit<'a>(
+        &'a self,
+        r: &Ray,
+        rt: &Interval,
+        record: &mut HitRecord<'a>,
+    ) -> bool {
+        let objects = &self.objects.objects;
+        self.bvh.hit(r, rt, record, |idx, r, rt, rec| {
+            objects[idx].hit(r, rt, rec)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Material;
+
+    // a point in the cube from -size to size on each axis
+    fn random_point(size: f64) -> Vec3 {
+        (Vec3::random_vector() * 2.0 - Vec3::new(1.0, 1.0, 1.0)) * size
+    }
+
+    // a seeded mix of every kind of primitive, overlapping at random
+    fn random_objects(count: usize) -> ObjectList {
+        let mut objects = ObjectList::new();
+        let mat = Material::new_diffuse(0.5, 0.5, 0.5);
+        for i in 0..count {
+            let p = random_point(10.0);
+            let size = 0.1 + random_double();
+            match i % 4 {
+                0 => objects.add_sphere(size, p.x, p.y, p.z, mat.clone()),
+                1 => objects.add_cube(size, p.x, p.y, p.z, mat.clone()),
+                2 => {
+                    let (u, v) = (random_point(size), random_point(size));
+                    objects.add_quad(p, u, v, mat.clone());
+                }
+                _ => {
+                    let (b, c) =
+                        (p + random_point(size), p + random_point(size));
+                    objects.add_triangle(p, b, c, mat.clone());
+                }
+            }
+        }
+        objects
+    }
+
+    fn closest<'a, P: Physical>(
+        objects: &'a P,
+        r: &Ray,
+    ) -> Option<HitRecord<'a>> {
+        let mut record = HitRecord::default();
+        let rt = Interval::new(0.001, f64::INFINITY);
+        objects.hit(r, &rt, &mut record).then_some(record)
+    }
+
+    // every ray hits the same point through the hierarchy as it does by
+    // trying each object in turn
+    fn assert_matches_linear_scan(objects: ObjectList, rays: usize) {
+        let bvh = ObjectBvh::new(objects.clone());
+        let mut hits = 0;
+        for _ in 0..rays {
+            let (origin, direction) =
+                (random_point(12.0), Vec3::random_unit_vector());
+            let r = Ray::new(origin, direction);
+            let expected = closest(&objects, &r);
+            let found = closest(&bvh, &r);
+            let ray = format!("ray from {origin:?} towards {direction:?}");
+            assert_eq!(found.is_some(), expected.is_some(), "{ray}");
+            if let (Some(found), Some(expected)) = (found, expected) {
+                hits += 1;
+                assert_eq!(found.t, expected.t, "{ray}");
+                assert_eq!(found.point, expected.point, "{ray}");
+                assert_eq!(found.normal, expected.normal, "{ray}");
+                assert_eq!((found.u, found.v), (expected.u, expected.v));
+            }
+        }
+        match objects.objects.is_empty() {
+            true => assert_eq!(hits, 0),
+            false => assert!(hits > 0, "no ray hit anything"),
+        }
+    }
+
+    #[test]
+    fn closest_hits_match_a_linear_scan() {
+        seed_rng(11);
+        assert_matches_linear_scan(random_objects(200), 5000);
+    }
+
+    #[test]
+    fn empty_and_single_object_hierarchies_match_a_linear_scan() {
+        seed_rng(12);
+        let empty = ObjectList::new();
+        assert_eq!(ObjectBvh::new(empty.clone()).stats().nodes, 0);
+        assert_matches_linear_scan(empty, 100);
+        let mut single = ObjectList::new();
+        let mat = Material::new_diffuse(0.5, 0.5, 0.5);
+        single.add_sphere(6.0, 0.0, 0.0, 0.0, mat);
+        assert_eq!(ObjectBvh::new(single.clone()).stats().leaves, 1);
+        assert_matches_linear_scan(single, 1000);
+    }
+}
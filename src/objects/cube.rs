@@ -13,47 +13,36 @@ pub struct Cube {
     sides: Vec<Quad>,
 }
 
+// outward normal, then the face axes pointing right and up when the face
+// is viewed from outside, in the order the faces appear in the cube map
+const FACES: [(Vec3, Vec3, Vec3); 6] = [
+    (X, NEG_Z, Y),
+    (NEG_X, Z, Y),
+    (Y, X, NEG_Z),
+    (NEG_Y, X, Z),
+    (Z, X, Y),
+    (NEG_Z, NEG_X, Y),
+];
+
+const X: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+const Y: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+const Z: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+const NEG_X: Vec3 = Vec3::new(-1.0, 0.0, 0.0);
+const NEG_Y: Vec3 = Vec3::new(0.0, -1.0, 0.0);
+const NEG_Z: Vec3 = Vec3::new(0.0, 0.0, -1.0);
+
 impl Cube {
+    // center is the corner with the smallest coordinates
     pub fn new(length: f64, center: Vec3, mat: Material) -> Self {
-        let far_corner =
-            Vec3::new(center.x + length, center.y + length, center.z + length);
-        let mut sides: Vec<Quad> = Vec::with_capacity(6);
-        sides.push(Quad::new(
-            center,
-            Vec3::new(0.0, length, 0.0),
-            Vec3::new(0.0, 0.0, length),
-            mat,
-        ));
-        sides.push(Quad::new(
-            center,
-            Vec3::new(0.0, length, 0.0),
-            Vec3::new(length, 0.0, 0.0),
-            mat,
-        ));
-        sides.push(Quad::new(
-            center,
-            Vec3::new(length, 0.0, 0.0),
-            Vec3::new(0.0, 0.0, length),
-            mat,
-        ));
-        sides.push(Quad::new(
-            far_corner,
-            Vec3::new(0.0, -length, 0.0),
-            Vec3::new(0.0, 0.0, -length),
-            mat,
-        ));
-        sides.push(Quad::new(
-            far_corner,
-            Vec3::new(0.0, -length, 0.0),
-            Vec3::new(-length, 0.0, 0.0),
-            mat,
-        ));
-        sides.push(Quad::new(
-            far_corner,
-            Vec3::new(-length, 0.0, 0.0),
-            Vec3::new(0.0, 0.0, -length),
-            mat,
-        ));
+        let half = 0.5 * length;
+        let middle = center + Vec3::new(half, half, half);
+        let sides = FACES
+            .iter()
+            .map(|&(normal, right, up)| {
+                let q = middle + half * normal - half * right - half * up;
+                Quad::new(q, length * right, length * up, mat.clone())
+            })
+            .collect();
         Self {
             length,
             center,
@@ -68,29 +57,75 @@ impl Cube {
 }
 
 impl Physical for Cube {
-    fn hit(&self, r: &Ray, rt: &Interval, record: &mut HitRecord) -> bool {
-        let quads = self.get_quads();
-        let mut tmp = HitRecord::default();
-        let mut hit = false;
-        let mut closest_q = 0;
-        record.t = std::f64::MAX;
-        for (q_idx, quad) in quads.iter().enumerate() {
-            if quad.hit(r, rt, &mut tmp) {
-                hit = true;
-                if tmp.t < record.t {
-                    record.t = tmp.t;
-                    closest_q = q_idx
-                }
+    // faces are laid out in a 3x2 grid of uv space, +x -x +y on the top
+    // row and -y +z -z on the bottom row
+    fn hit<'a>(
+        &'a self,
+        r: &Ray,
+        rt: &Interval,
+        record: &mut HitRecord<'a>,
+    ) -> bool {
+        let mut closest = Interval::new(rt.min, rt.max);
+        let mut face = None;
+        for (q_idx, quad) in self.get_quads().iter().enumerate() {
+            if quad.hit(r, &closest, record) {
+                closest.max = record.t;
+                face = Some(q_idx);
             }
         }
-        if !hit {
+        let Some(face) = face else {
             return false;
+        };
+        let (col, row) = ((face % 3) as f64, (1 - face / 3) as f64);
+        record.u = (col + record.u) / 3.0;
+        record.v = (row + record.v) / 2.0;
+        record.mat = Some(&self.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let l = Vec3::new(self.length, self.length, self.length);
+        Aabb::new(self.center, self.center + l)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a cube of side 2 around the origin, hit from outside along the
+    // outward normal at the point offset by (right, up) from a face centre
+    fn hit_face(cube: &Cube, normal: Vec3, offset: Vec3) -> HitRecord<'_> {
+        let r = Ray::new(3.0 * normal + offset, -1.0 * normal);
+        let mut record = HitRecord::default();
+        let rt = Interval::new(0.001, f64::INFINITY);
+        assert!(cube.hit(&r, &rt, &mut record));
+        record
+    }
+
+    #[test]
+    fn faces_fill_a_three_by_two_grid() {
+        let mat = Material::new_diffuse(0.5, 0.5, 0.5);
+        let cube = Cube::new(2.0, Vec3::new(-1.0, -1.0, -1.0), mat);
+        // column and row of each face's cell, rows counted from the bottom
+        let cells = [
+            (X, 0.0, 1.0),
+            (NEG_X, 1.0, 1.0),
+            (Y, 2.0, 1.0),
+            (NEG_Y, 0.0, 0.0),
+            (Z, 1.0, 0.0),
+            (NEG_Z, 2.0, 0.0),
+        ];
+        for ((normal, col, row), (_, right, up)) in cells.into_iter().zip(FACES)
+        {
+            // a quarter of the way in from the face's top right corner
+            let record = hit_face(&cube, normal, 0.5 * right + 0.5 * up);
+            let (u, v) = ((col + 0.75) / 3.0, (row + 0.75) / 2.0);
+            assert!((record.u - u).abs() < 1e-9, "{normal:?}: u {}", record.u);
+            assert!((record.v - v).abs() < 1e-9, "{normal:?}: v {}", record.v);
+            assert_eq!(record.normal, normal);
+            assert!((record.tangent - right).length() < 1e-9, "{normal:?}");
+            assert!((record.bitangent - up).length() < 1e-9, "{normal:?}");
         }
-        let cross = Vec3::cross(quads[closest_q].u, quads[closest_q].v);
-        let normal = Vec3::unit_vector(cross);
-        record.set_face_normal(r, normal);
-        record.point = r.at(record.t);
-        record.mat = Some(self.mat);
-        return true;
     }
 }
@@ -0,0 +1,76 @@
+use crate::math::*;
+
+use super::objects::Object;
+use super::HitRecord;
+use super::ObjectList;
+use super::Physical;
+
+// the emissive objects of a scene that can be sampled directly, each
+// chosen with equal probability
+#[derive(Debug, Clone)]
+pub struct LightList {
+    lights: Vec<Object>,
+}
+
+impl LightList {
+    pub fn new(objects: &ObjectList) -> Self {
+        let lights = objects
+            .objects
+            .iter()
+            .filter(|obj| obj.is_light())
+            .cloned()
+            .collect();
+        Self { lights }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self
+            .lights
+            .iter()
+            .map(|light| light.pdf_value(origin, direction))
+            .sum();
+        total / self.lights.len() as f64
+    }
+
+    // density with which random_point picks the direction of r, counting
+    // only the light that r actually reaches at t. zero when whatever is
+    // there is not one of the sampled lights, e.g. an emissive mesh
+    pub fn pdf_hit(&self, r: &Ray, t: f64) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let rt = Interval::new(0.001, f64::INFINITY);
+        let reaches = |light: &&Object| {
+            let mut rec = HitRecord::default();
+            light.hit(r, &rt, &mut rec)
+                && (rec.t - t).abs() <= 1e-6 * t.max(1.0)
+        };
+        let total: f64 = self
+            .lights
+            .iter()
+            .filter(reaches)
+            .map(|light| light.pdf_value(r.origin, r.direction))
+            .sum();
+        total / self.lights.len() as f64
+    }
+
+    pub fn random_point(&self, origin: Vec3) -> Option<Vec3> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let idx = (random_double() * self.lights.len() as f64) as usize;
+        let light = &self.lights[idx.min(self.lights.len() - 1)];
+        Some(light.random_point(origin))
+    }
+}
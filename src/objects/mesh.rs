@@ -0,0 +1,311 @@
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::materials::Material;
+use crate::math::*;
+
+use super::triangle;
+use super::Bvh;
+use super::HitRecord;
+use super::Physical;
+use super::Triangle;
+
+type Corners = ([Vec3; 3], Option<[Vec3; 3]>, Option<[(f64, f64); 3]>);
+
+// indexes of a single triangle corner into the mesh buffers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaceIndex {
+    pub vertex: usize,
+    pub normal: Option<usize>,
+    pub uv: Option<usize>,
+}
+
+impl FaceIndex {
+    pub fn new(
+        vertex: usize,
+        normal: Option<usize>,
+        uv: Option<usize>,
+    ) -> Self {
+        Self { vertex, normal, uv }
+    }
+}
+
+// a face corner that points past the end of one of the mesh buffers
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshError {
+    pub face: usize,
+    pub buffer: &'static str,
+    pub index: usize,
+    pub len: usize,
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            face,
+            buffer,
+            index,
+            len,
+        } = self;
+        write!(f, "face {face} uses {buffer} {index} but {len} are defined")
+    }
+}
+
+impl Error for MeshError {}
+
+// vertex, normal and uv buffers are reference counted so that several
+// meshes (e.g. groups with different materials) can share the same data,
+// geometry is immutable once built since the bvh is derived from it
+#[derive(Debug, Clone)]
+pub struct Mesh {
+    vertices: Arc<Vec<Vec3>>,
+    normals: Arc<Vec<Vec3>>,
+    uvs: Arc<Vec<(f64, f64)>>,
+    faces: Vec<[FaceIndex; 3]>,
+    pub mat: Material,
+    // obj file the mesh was loaded from, if any, so that a saved scene can
+    // refer to the file rather than inlining the geometry
+    pub path: Option<PathBuf>,
+    bvh: Bvh,
+}
+
+impl Mesh {
+    pub fn new(
+        vertices: Arc<Vec<Vec3>>,
+        normals: Arc<Vec<Vec3>>,
+        uvs: Arc<Vec<(f64, f64)>>,
+        faces: Vec<[FaceIndex; 3]>,
+        mat: Material,
+    ) -> Result<Self, MeshError> {
+        for (face, corners) in faces.iter().enumerate() {
+            for corner in corners {
+                let check = |buffer, index: Option<usize>, len| match index {
+                    Some(index) if index >= len => Err(MeshError {
+                        face,
+                        buffer,
+                        index,
+                        len,
+                    }),
+                    _ => Ok(()),
+                };
+                check("vertex", Some(corner.vertex), vertices.len())?;
+                check("normal", corner.normal, normals.len())?;
+                check("uv", corner.uv, uvs.len())?;
+            }
+        }
+        let bounds: Vec<Aabb> = faces
+            .iter()
+            .map(|f| Aabb::from_points(&f.map(|i| vertices[i.vertex])))
+            .map(|b| b.padded(1e-4))
+            .collect();
+        Ok(Self {
+            vertices,
+            normals,
+            uvs,
+            faces,
+            mat,
+            path: None,
+            bvh: Bvh::new(&bounds),
+        })
+    }
+
+    // builds a flat shaded mesh from positions and vertex index triples
+    pub fn from_indices(
+        vertices: Vec<Vec3>,
+        indices: Vec<[usize; 3]>,
+        mat: Material,
+    ) -> Result<Self, MeshError> {
+        let faces = indices
+            .iter()
+            .map(|f| f.map(|i| FaceIndex::new(i, None, None)))
+            .collect();
+        Self::new(
+            Arc::new(vertices),
+            Arc::new(Vec::new()),
+            Arc::new(Vec::new()),
+            faces,
+            mat,
+        )
+    }
+
+    pub fn vertices(&self) -> &Arc<Vec<Vec3>> {
+        &self.vertices
+    }
+
+    pub fn normals(&self) -> &Arc<Vec<Vec3>> {
+        &self.normals
+    }
+
+    pub fn uvs(&self) -> &Arc<Vec<(f64, f64)>> {
+        &self.uvs
+    }
+
+    pub fn faces(&self) -> &[[FaceIndex; 3]] {
+        &self.faces
+    }
+
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    // resolves a face into its corner positions, normals and uvs
+    fn corners(&self, idx: usize) -> Corners {
+        let [a, b, c] = self.faces[idx];
+        let v = &self.vertices;
+        let positions = [v[a.vertex], v[b.vertex], v[c.vertex]];
+        let normals = match (a.normal, b.normal, c.normal) {
+            (Some(na), Some(nb), Some(nc)) => {
+                let n = &self.normals;
+                Some([n[na], n[nb], n[nc]])
+            }
+            _ => None,
+        };
+        let uvs = match (a.uv, b.uv, c.uv) {
+            (Some(ta), Some(tb), Some(tc)) => {
+                let t = &self.uvs;
+                Some([t[ta], t[tb], t[tc]])
+            }
+            _ => None,
+        };
+        (positions, normals, uvs)
+    }
+
+    pub fn triangle(&self, idx: usize) -> Triangle {
+        let ([a, b, c], normals, uvs) = self.corners(idx);
+        let mut tri = Triangle::new(a, b, c, self.mat.clone());
+        tri.normals = normals;
+        tri.uvs = uvs;
+        tri
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = Triangle> + '_ {
+        (0..self.faces.len()).map(|idx| self.triangle(idx))
+    }
+}
+
+impl Physical for Mesh {
+    fn hit<'a>(
+        &'a self,
+        r: &Ray,
+        rt: &Interval,
+        record: &mut HitRecord<'a>,
+    ) -> bool {
+        let hit = self.bvh.hit(r, rt, record, |idx, r, rt, rec| {
+            let (positions, normals, uvs) = self.corners(idx);
+            triangle::hit_surface(positions, normals, uvs, r, rt, rec)
+        });
+        if hit {
+            record.mat = Some(&self.mat);
+        }
+        hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh(normals: Vec<Vec3>, uvs: Vec<(f64, f64)>) -> Mesh {
+        let vertices = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let has_normals = !normals.is_empty();
+        let has_uvs = !uvs.is_empty();
+        let face = [0, 1, 2].map(|i| {
+            FaceIndex::new(i, has_normals.then_some(i), has_uvs.then_some(i))
+        });
+        Mesh::new(
+            Arc::new(vertices),
+            Arc::new(normals),
+            Arc::new(uvs),
+            vec![face],
+            Material::new_diffuse(0.5, 0.5, 0.5),
+        )
+        .unwrap()
+    }
+
+    // straight down onto the point with barycentrics (0.5, 0.25, 0.25)
+    fn hit(mesh: &Mesh) -> HitRecord<'_> {
+        let r = Ray::new(Vec3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut record = HitRecord::default();
+        let rt = Interval::new(0.001, f64::INFINITY);
+        assert!(mesh.hit(&r, &rt, &mut record));
+        record
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn hit_without_uvs_reports_barycentrics() {
+        let mesh = mesh(Vec::new(), Vec::new());
+        let record = hit(&mesh);
+        assert!((record.t - 1.0).abs() < 1e-9);
+        assert!((record.u - 0.25).abs() < 1e-9);
+        assert!((record.v - 0.25).abs() < 1e-9);
+        assert_close(record.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(record.front_facing);
+        assert!(record.mat.is_some());
+    }
+
+    #[test]
+    fn hit_interpolates_normals_and_uvs() {
+        let normals = vec![
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 1.0),
+            Vec3::new(0.0, 1.0, 1.0),
+        ];
+        let uvs = vec![(0.2, 0.4), (1.0, 0.4), (0.2, 1.0)];
+        let mesh = mesh(normals, uvs);
+        let record = hit(&mesh);
+        let expected = Vec3::unit_vector(Vec3::new(0.25, 0.25, 1.0));
+        assert_close(record.normal, expected);
+        assert!((record.u - 0.4).abs() < 1e-9);
+        assert!((record.v - 0.55).abs() < 1e-9);
+    }
+
+    #[test]
+    fn new_rejects_out_of_range_indexes() {
+        let vertices = Arc::new(vec![Vec3::default(); 3]);
+        let normals = Arc::new(vec![Vec3::new(0.0, 0.0, 1.0)]);
+        let bad = |vertex, normal| {
+            let mut face = [0, 1, 2].map(|i| FaceIndex::new(i, None, None));
+            face[2] = FaceIndex::new(vertex, normal, None);
+            Mesh::new(
+                Arc::clone(&vertices),
+                Arc::clone(&normals),
+                Arc::new(Vec::new()),
+                vec![face, face],
+                Material::new_diffuse(0.5, 0.5, 0.5),
+            )
+            .unwrap_err()
+        };
+        let err = bad(3, None);
+        assert_eq!((err.face, err.buffer, err.index), (0, "vertex", 3));
+        let err = bad(2, Some(1));
+        assert_eq!((err.buffer, err.index, err.len), ("normal", 1, 1));
+        let missing_uv = [0, 1, 2].map(|i| FaceIndex::new(i, None, Some(0)));
+        let err = Mesh::new(
+            vertices,
+            normals,
+            Arc::new(Vec::new()),
+            vec![missing_uv],
+            Material::new_diffuse(0.5, 0.5, 0.5),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "face 0 uses uv 0 but 0 are defined");
+    }
+}
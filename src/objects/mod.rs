@@ -1,18 +1,33 @@
 // modules
 pub mod record;
 
+#[allow(clippy::module_inception)]
 pub mod objects;
 
+pub mod bvh;
 pub mod cube;
+pub mod lights;
+pub mod mesh;
 pub mod quad;
 pub mod sphere;
+pub mod triangle;
 
 // flatten
 pub use record::HitRecord;
 
+pub use objects::Object;
 pub use objects::ObjectList;
 pub use objects::Physical;
 
+pub use bvh::Bvh;
+pub use bvh::BvhStats;
+pub use bvh::ObjectBvh;
+pub use lights::LightList;
+
 pub use cube::Cube;
+pub use mesh::FaceIndex;
+pub use mesh::Mesh;
+pub use mesh::MeshError;
 pub use quad::Quad;
 pub use sphere::Sphere;
+pub use triangle::Triangle;
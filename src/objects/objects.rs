@@ -8,6 +8,12 @@ pub struct ObjectList {
     pub objects: Vec<Object>,
 }
 
+impl Default for ObjectList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ObjectList {
     #[allow(dead_code)]
     pub fn new() -> Self {
@@ -46,6 +52,15 @@ impl ObjectList {
         let quad = Object::Quad(Quad::new(q, u, v, mat));
         self.objects.push(quad);
     }
+
+    pub fn add_triangle(&mut self, a: Vec3, b: Vec3, c: Vec3, mat: Material) {
+        let tri = Object::Triangle(Triangle::new(a, b, c, mat));
+        self.objects.push(tri);
+    }
+
+    pub fn add_mesh(&mut self, mesh: Mesh) {
+        self.objects.push(Object::Mesh(mesh));
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -53,17 +68,115 @@ pub enum Object {
     Sphere(Sphere),
     Cube(Cube),
     Quad(Quad),
+    Triangle(Triangle),
+    Mesh(Mesh),
 }
 pub trait Physical {
-    fn hit(&self, r: &Ray, rt: &Interval, record: &mut HitRecord) -> bool;
+    fn hit<'a>(
+        &'a self,
+        r: &Ray,
+        rt: &Interval,
+        record: &mut HitRecord<'a>,
+    ) -> bool;
+
+    fn bounding_box(&self) -> Aabb;
+
+    // solid angle density of sampling `direction` from `origin` with
+    // random_point, shapes that cannot be sampled as lights report zero
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    fn random_point(&self, _origin: Vec3) -> Vec3 {
+        self.bounding_box().centroid()
+    }
 }
 
 impl Physical for Object {
-    fn hit(&self, r: &Ray, rt: &Interval, record: &mut HitRecord) -> bool {
+    fn hit<'a>(
+        &'a self,
+        r: &Ray,
+        rt: &Interval,
+        record: &mut HitRecord<'a>,
+    ) -> bool {
         match self {
             Self::Sphere(obj) => obj.hit(r, rt, record),
             Self::Quad(obj) => obj.hit(r, rt, record),
             Self::Cube(obj) => obj.hit(r, rt, record),
+            Self::Triangle(obj) => obj.hit(r, rt, record),
+            Self::Mesh(obj) => obj.hit(r, rt, record),
         }
     }
+
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            Self::Sphere(obj) => obj.bounding_box(),
+            Self::Quad(obj) => obj.bounding_box(),
+            Self::Cube(obj) => obj.bounding_box(),
+            Self::Triangle(obj) => obj.bounding_box(),
+            Self::Mesh(obj) => obj.bounding_box(),
+        }
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        match self {
+            Self::Sphere(obj) => obj.pdf_value(origin, direction),
+            Self::Quad(obj) => obj.pdf_value(origin, direction),
+            _ => 0.0,
+        }
+    }
+
+    fn random_point(&self, origin: Vec3) -> Vec3 {
+        match self {
+            Self::Sphere(obj) => obj.random_point(origin),
+            Self::Quad(obj) => obj.random_point(origin),
+            _ => self.bounding_box().centroid(),
+        }
+    }
+}
+
+impl Object {
+    pub fn material(&self) -> &Material {
+        match self {
+            Self::Sphere(obj) => &obj.mat,
+            Self::Quad(obj) => &obj.mat,
+            Self::Cube(obj) => &obj.mat,
+            Self::Triangle(obj) => &obj.mat,
+            Self::Mesh(obj) => &obj.mat,
+        }
+    }
+
+    // emissive shapes that can be sampled directly by the renderer
+    pub fn is_light(&self) -> bool {
+        let samplable = matches!(self, Self::Sphere(_) | Self::Quad(_));
+        samplable && matches!(self.material(), Material::Emissive(_))
+    }
+}
+
+// linear scan over every object, see ObjectBvh for the accelerated version
+impl Physical for ObjectList {
+    fn hit<'a>(
+        &'a self,
+        r: &Ray,
+        rt: &Interval,
+        record: &mut HitRecord<'a>,
+    ) -> bool {
+        let mut hit = false;
+        let mut tmp = HitRecord::default();
+        let mut closest = Interval::new(rt.min, rt.max);
+        for object in &self.objects {
+            if object.hit(r, &closest, &mut tmp) {
+                hit = true;
+                closest.max = tmp.t;
+                *record = tmp;
+            }
+        }
+        hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.objects
+            .iter()
+            .fold(Aabb::empty(), |acc, o| acc.union(&o.bounding_box()))
+    }
 }
@@ -29,15 +29,21 @@ impl Quad {
 }
 
 impl Physical for Quad {
-    fn hit(&self, r: &Ray, rt: &Interval, record: &mut HitRecord) -> bool {
-        let normal = Vec3::unit_vector(Vec3::cross(self.u, self.v));
-        let w = normal / (normal * normal);
+    fn hit<'a>(
+        &'a self,
+        r: &Ray,
+        rt: &Interval,
+        record: &mut HitRecord<'a>,
+    ) -> bool {
+        let n = Vec3::cross(self.u, self.v);
+        let normal = Vec3::unit_vector(n);
+        let w = n / (n * n);
         let quot = normal * r.direction;
         if quot.abs() < 1e-8 {
             return false;
         }
         let t = ((normal * self.q) - (normal * r.origin)) / quot;
-        if rt.contains(t) == false {
+        if !rt.contains(t) {
             return false;
         }
         let intersection = r.at(t);
@@ -49,8 +55,62 @@ impl Physical for Quad {
         }
         record.t = t;
         record.point = intersection;
-        record.mat = Some(self.mat);
+        record.tangent = Vec3::unit_vector(self.u);
+        record.bitangent = Vec3::unit_vector(self.v);
+        record.mat = Some(&self.mat);
         record.set_face_normal(r, normal);
-        return true;
+        true
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        let rt = Interval::new(0.001, f64::INFINITY);
+        if !self.hit(&Ray::new(origin, direction), &rt, &mut rec) {
+            return 0.0;
+        }
+        let area = Vec3::cross(self.u, self.v).length();
+        let distance_squared = rec.t * rec.t * direction.len_squared();
+        let cosine = (direction * rec.normal).abs() / direction.length();
+        distance_squared / (cosine * area)
+    }
+
+    fn random_point(&self, _origin: Vec3) -> Vec3 {
+        self.q + random_double() * self.u + random_double() * self.v
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let (q, u, v) = (self.q, self.u, self.v);
+        Aabb::from_points(&[q, q + u, q + v, q + u + v]).padded(1e-4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 2x2 quad whose edges are not unit length, so |u x v| = 4
+    fn quad() -> Quad {
+        let mat = Material::new_diffuse(0.5, 0.5, 0.5);
+        let u = Vec3::new(2.0, 0.0, 0.0);
+        let v = Vec3::new(0.0, 2.0, 0.0);
+        Quad::new(Vec3::default(), u, v, mat)
+    }
+
+    fn hit_at(quad: &Quad, x: f64, y: f64) -> Option<HitRecord<'_>> {
+        let r = Ray::new(Vec3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut record = HitRecord::default();
+        let rt = Interval::new(0.001, f64::INFINITY);
+        quad.hit(&r, &rt, &mut record).then_some(record)
+    }
+
+    #[test]
+    fn hits_cover_the_whole_of_a_large_quad() {
+        let quad = quad();
+        let record = hit_at(&quad, 1.5, 0.5).expect("inside the quad");
+        assert!((record.u - 0.75).abs() < 1e-9);
+        assert!((record.v - 0.25).abs() < 1e-9);
+        assert!(hit_at(&quad, 1.9, 1.9).is_some());
+        assert!(hit_at(&quad, 2.1, 1.0).is_none());
+        assert!(hit_at(&quad, 1.0, -0.1).is_none());
     }
 }
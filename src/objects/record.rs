@@ -1,30 +1,39 @@
 use crate::materials::Material;
 use crate::math::*;
 
+// the material is borrowed from the object that was hit, so records stay
+// cheap to copy while searching for the closest hit
 #[derive(Debug, Clone, Copy)]
-pub struct HitRecord {
+pub struct HitRecord<'a> {
     pub point: Vec3,
     pub normal: Vec3,
     pub u: f64,
     pub v: f64,
+    // unit surface derivatives along u and v
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
     pub t: f64,
-    pub mat: Option<Material>,
+    pub mat: Option<&'a Material>,
     pub front_facing: bool,
 }
 
-impl HitRecord {
-    pub fn default() -> Self {
+impl Default for HitRecord<'_> {
+    fn default() -> Self {
         HitRecord {
             point: Vec3::default(),
             normal: Vec3::default(),
             u: 0.0,
             v: 0.0,
+            tangent: Vec3::default(),
+            bitangent: Vec3::default(),
             t: 0.0,
             mat: None,
             front_facing: false,
         }
     }
+}
 
+impl HitRecord<'_> {
     pub fn set_face_normal(&mut self, ray: &Ray, outward_normal: Vec3) {
         let normal_direction = ray.direction * outward_normal < 0.0;
         (self.normal, self.front_facing) = match normal_direction {
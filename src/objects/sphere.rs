@@ -1,4 +1,6 @@
 use crate::materials::Material;
+use std::f64::consts::PI;
+
 use crate::math::*;
 
 use super::HitRecord;
@@ -21,8 +23,30 @@ impl Sphere {
     }
 }
 
+impl Sphere {
+    // longitude and latitude, u = 0 at -x going around through +z, +x and
+    // -z and v running from the south to the north pole
+    fn set_uv(&self, p: Vec3, record: &mut HitRecord) {
+        let theta = (-p.y).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z).atan2(p.x) + PI;
+        record.u = phi / (2.0 * PI);
+        record.v = theta / PI;
+        record.tangent = Vec3::new(phi.sin(), 0.0, phi.cos());
+        record.bitangent = Vec3::new(
+            -phi.cos() * theta.cos(),
+            theta.sin(),
+            phi.sin() * theta.cos(),
+        );
+    }
+}
+
 impl Physical for Sphere {
-    fn hit(&self, r: &Ray, rt: &Interval, record: &mut HitRecord) -> bool {
+    fn hit<'a>(
+        &'a self,
+        r: &Ray,
+        rt: &Interval,
+        record: &mut HitRecord<'a>,
+    ) -> bool {
         let oc = self.center - r.origin;
         let a = r.direction * r.direction;
         let h = r.direction * oc;
@@ -32,17 +56,172 @@ impl Physical for Sphere {
             return false;
         }
         let mut root = (h - discriminant.sqrt()) / a;
-        if rt.surrounds(root) == false {
+        if !rt.surrounds(root) {
             root = (h + discriminant.sqrt()) / a;
-            if rt.surrounds(root) == false {
+            if !rt.surrounds(root) {
                 return false;
             }
         }
         record.t = root;
         record.point = r.at(root);
-        record.mat = Some(self.mat);
+        record.mat = Some(&self.mat);
         let outward_normal = (r.at(root) - self.center) / self.radius;
+        self.set_uv(outward_normal, record);
         record.set_face_normal(r, outward_normal);
-        return true;
+        true
+    }
+
+    // samples the cone of directions subtended by the sphere, or the full
+    // sphere of directions when the origin is inside it
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        let rt = Interval::new(0.001, f64::INFINITY);
+        if !self.hit(&Ray::new(origin, direction), &rt, &mut rec) {
+            return 0.0;
+        }
+        let distance_squared = (self.center - origin).len_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 1.0 / (4.0 * PI);
+        }
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn random_point(&self, origin: Vec3) -> Vec3 {
+        let to_center = self.center - origin;
+        let distance_squared = to_center.len_squared();
+        let radius_squared = self.radius * self.radius;
+        let direction = match distance_squared <= radius_squared {
+            true => Vec3::random_unit_vector(),
+            false => {
+                let cos_theta_max =
+                    (1.0 - radius_squared / distance_squared).sqrt();
+                let (r1, r2) = (random_double(), random_double());
+                let z = 1.0 + r2 * (cos_theta_max - 1.0);
+                let phi = 2.0 * PI * r1;
+                let sin_theta = (1.0 - z * z).sqrt();
+                let w = Vec3::unit_vector(to_center);
+                let a = match w.x.abs() > 0.9 {
+                    true => Vec3::new(0.0, 1.0, 0.0),
+                    false => Vec3::new(1.0, 0.0, 0.0),
+                };
+                let v = Vec3::unit_vector(Vec3::cross(w, a));
+                let u = Vec3::cross(w, v);
+                (phi.cos() * sin_theta) * u
+                    + (phi.sin() * sin_theta) * v
+                    + z * w
+            }
+        };
+        let mut rec = HitRecord::default();
+        let rt = Interval::new(0.0, f64::INFINITY);
+        match self.hit(&Ray::new(origin, direction), &rt, &mut rec) {
+            true => rec.point,
+            // grazing directions can miss through rounding
+            false => self.center - self.radius * Vec3::unit_vector(to_center),
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.center - r, self.center + r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // where a ray aimed at the centre of a unit sphere from outside along
+    // the given outward direction hits
+    fn hit_from(sphere: &Sphere, outward: Vec3) -> HitRecord<'_> {
+        let r = Ray::new(3.0 * outward, -1.0 * outward);
+        let mut record = HitRecord::default();
+        let rt = Interval::new(0.001, f64::INFINITY);
+        assert!(sphere.hit(&r, &rt, &mut record));
+        record
+    }
+
+    fn sphere() -> Sphere {
+        Sphere::new(1.0, Vec3::default(), Material::new_diffuse(0.5, 0.5, 0.5))
+    }
+
+    // tangent and bitangent are unit length and at right angles to each
+    // other and the normal
+    fn assert_orthonormal(record: &HitRecord) {
+        let (n, t, b) = (record.normal, record.tangent, record.bitangent);
+        assert!((t.length() - 1.0).abs() < 1e-9, "tangent {t:?}");
+        assert!((b.length() - 1.0).abs() < 1e-9, "bitangent {b:?}");
+        assert!((t * n).abs() < 1e-9 && (b * n).abs() < 1e-9);
+        assert!((t * b).abs() < 1e-9);
+    }
+
+    #[test]
+    fn longitude_and_latitude_follow_the_axes() {
+        let sphere = sphere();
+        let cases = [
+            (Vec3::new(0.0, 0.0, 1.0), 0.25),
+            (Vec3::new(1.0, 0.0, 0.0), 0.5),
+            (Vec3::new(0.0, 0.0, -1.0), 0.75),
+        ];
+        for (outward, u) in cases {
+            let record = hit_from(&sphere, outward);
+            assert!((record.u - u).abs() < 1e-9, "{outward:?}: u {}", record.u);
+            assert!(
+                (record.v - 0.5).abs() < 1e-9,
+                "{outward:?}: v {}",
+                record.v
+            );
+            assert_orthonormal(&record);
+        }
+        let record =
+            hit_from(&sphere, Vec3::unit_vector(Vec3::new(1.0, 1.0, 0.0)));
+        assert!((record.u - 0.5).abs() < 1e-9);
+        assert!((record.v - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tangents_point_along_increasing_u_and_v() {
+        let sphere = sphere();
+        let outward = Vec3::unit_vector(Vec3::new(0.3, -0.4, 0.8));
+        let record = hit_from(&sphere, outward);
+        assert_orthonormal(&record);
+        // a small step along each tangent moves u or v up and not the other
+        let step = |direction: Vec3| {
+            hit_from(
+                &sphere,
+                Vec3::unit_vector(record.point + 1e-4 * direction),
+            )
+        };
+        let along_u = step(record.tangent);
+        assert!(along_u.u > record.u && (along_u.v - record.v).abs() < 1e-7);
+        let along_v = step(record.bitangent);
+        assert!(along_v.v > record.v && (along_v.u - record.u).abs() < 1e-7);
+    }
+
+    #[test]
+    fn poles_have_the_extreme_latitudes_and_a_usable_frame() {
+        let sphere = sphere();
+        let north = hit_from(&sphere, Vec3::new(0.0, 1.0, 0.0));
+        let south = hit_from(&sphere, Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!((north.v, south.v), (1.0, 0.0));
+        for record in [north, south] {
+            assert!((0.0..=1.0).contains(&record.u));
+            assert_orthonormal(&record);
+        }
+    }
+
+    #[test]
+    fn u_wraps_around_at_the_seam() {
+        let sphere = sphere();
+        let before =
+            hit_from(&sphere, Vec3::unit_vector(Vec3::new(-1.0, 0.0, -1e-6)));
+        let after =
+            hit_from(&sphere, Vec3::unit_vector(Vec3::new(-1.0, 0.0, 1e-6)));
+        assert!(before.u > 1.0 - 1e-6 && before.u <= 1.0, "u {}", before.u);
+        assert!(after.u >= 0.0 && after.u < 1e-6, "u {}", after.u);
+        // the frame is continuous across the seam even though u jumps
+        assert!((before.tangent - after.tangent).length() < 1e-5);
+        assert!((before.bitangent - after.bitangent).length() < 1e-5);
     }
 }
@@ -0,0 +1,155 @@
+use crate::materials::Material;
+use crate::math::*;
+
+use super::HitRecord;
+use super::Physical;
+
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: Option<[(f64, f64); 3]>,
+    pub mat: Material,
+}
+
+impl Triangle {
+    pub fn new(a: Vec3, b: Vec3, c: Vec3, mat: Material) -> Self {
+        Self {
+            a,
+            b,
+            c,
+            normals: None,
+            uvs: None,
+            mat,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    pub fn geometric_normal(&self) -> Vec3 {
+        Vec3::unit_vector(Vec3::cross(self.b - self.a, self.c - self.a))
+    }
+}
+
+// moller-trumbore, returns (t, b1, b2) where the hit point is
+// a * (1 - b1 - b2) + b * b1 + c * b2
+fn intersect(
+    [a, b, c]: [Vec3; 3],
+    r: &Ray,
+    rt: &Interval,
+) -> Option<(f64, f64, f64)> {
+    let (e1, e2) = (b - a, c - a);
+    let p = Vec3::cross(r.direction, e2);
+    let det = e1 * p;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = r.origin - a;
+    let b1 = (s * p) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let q = Vec3::cross(s, e1);
+    let b2 = (r.direction * q) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = (e2 * q) * inv_det;
+    if !rt.surrounds(t) {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+// fills in everything but the material, so meshes only need to attach
+// theirs once the closest triangle is known
+pub(super) fn hit_surface(
+    corners: [Vec3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    r: &Ray,
+    rt: &Interval,
+    record: &mut HitRecord,
+) -> bool {
+    let Some((t, b1, b2)) = intersect(corners, r, rt) else {
+        return false;
+    };
+    let [a, b, c] = corners;
+    let b0 = 1.0 - b1 - b2;
+    let (e1, e2) = (b - a, c - a);
+    let geometric = Vec3::unit_vector(Vec3::cross(e1, e2));
+    let normal = match normals {
+        Some([na, nb, nc]) => {
+            let n = Vec3::unit_vector(b0 * na + b1 * nb + b2 * nc);
+            match n * geometric < 0.0 {
+                true => -1.0 * n,
+                false => n,
+            }
+        }
+        None => geometric,
+    };
+    (record.u, record.v) = match uvs {
+        Some([ua, ub, uc]) => (
+            b0 * ua.0 + b1 * ub.0 + b2 * uc.0,
+            b0 * ua.1 + b1 * ub.1 + b2 * uc.1,
+        ),
+        None => (b1, b2),
+    };
+    // solve e1 = du1 * dp/du + dv1 * dp/dv (and likewise for e2), which
+    // without uvs reduces to the two edges
+    let (dpdu, dpdv) = match uvs {
+        Some([ua, ub, uc]) => {
+            let (du1, dv1) = (ub.0 - ua.0, ub.1 - ua.1);
+            let (du2, dv2) = (uc.0 - ua.0, uc.1 - ua.1);
+            let det = du1 * dv2 - du2 * dv1;
+            match det.abs() < 1e-12 {
+                true => (e1, e2),
+                false => {
+                    ((dv2 * e1 - dv1 * e2) / det, (du1 * e2 - du2 * e1) / det)
+                }
+            }
+        }
+        None => (e1, e2),
+    };
+    record.tangent = Vec3::unit_vector(dpdu);
+    record.bitangent = Vec3::unit_vector(dpdv);
+    record.t = t;
+    record.point = r.at(t);
+    record.front_facing = r.direction * geometric < 0.0;
+    record.normal = match record.front_facing {
+        true => normal,
+        false => -1.0 * normal,
+    };
+    true
+}
+
+impl Physical for Triangle {
+    fn hit<'a>(
+        &'a self,
+        r: &Ray,
+        rt: &Interval,
+        record: &mut HitRecord<'a>,
+    ) -> bool {
+        let corners = [self.a, self.b, self.c];
+        if !hit_surface(corners, self.normals, self.uvs, r, rt, record) {
+            return false;
+        }
+        record.mat = Some(&self.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(&[self.a, self.b, self.c]).padded(1e-4)
+    }
+}
@@ -0,0 +1,28 @@
+use crate::math::*;
+
+// radiance returned for rays that escape the scene
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Background {
+    Gradient { from: Vec3, to: Vec3 },
+    Solid(Vec3),
+    None,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::Gradient {
+            from: Vec3::new(0.5, 0.7, 1.0),
+            to: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Background {
+    pub fn color(&self, r: &Ray) -> Vec3 {
+        match self {
+            Self::Gradient { from, to } => lerp(r, *from, *to),
+            Self::Solid(color) => *color,
+            Self::None => Vec3::default(),
+        }
+    }
+}
@@ -1,28 +1,196 @@
-use crate::math::Vec3;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt;
 
-#[derive(Debug, Copy, Clone)]
+use crate::math::*;
+
+use super::Filter;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    // parallel rays through a viewport of the given world space height
+    Orthographic {
+        height: f64,
+    },
+    // full sphere of directions, longitude across and latitude down
+    Equirectangular,
+    // equidistant fisheye covering fov degrees across the inscribed circle
+    Fisheye {
+        fov: f64,
+    },
+    // six square faces in a 3x2 grid, +x -x +y on the top row and
+    // -y +z -z on the bottom, in the camera frame of x right, y up and
+    // z backward so the -z face looks at the target
+    CubeMap,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum StereoMode {
+    // eyes share the view direction and the frustums are shifted so the
+    // images line up at the convergence distance
+    #[default]
+    Parallel,
+    // each eye is rotated to look at the convergence point
+    ToeIn,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stereo {
+    pub mode: StereoMode,
+    // distance between the eyes in world units
+    pub separation: f64,
+    // distance at which both eyes see the same point, zero parallax
+    pub convergence: f64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+// view direction and the up vector of each cube map face in camera space
+const CUBE_FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+    (Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+    (Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+    (Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+    (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0)),
+    (Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0)),
+];
+
+#[derive(Debug, Default, Copy, Clone)]
 pub struct Camera {
     pub position: Vec3,
     pub target: Vec3,
+    pub forward: Vec3,
+    pub right: Vec3,
+    pub up: Vec3,
+    pub projection: Projection,
     pub pixel_delta_u: Vec3,
     pub pixel_delta_v: Vec3,
     pub pixel_origin: Vec3,
+    // lens disk axes scaled by its radius, zero for a pinhole camera
+    pub defocus_disk_u: Vec3,
+    pub defocus_disk_v: Vec3,
     pub image_width: usize,
     pub image_height: usize,
     pub samples: usize,
     pub max_depth: usize,
+    pub filter: Filter,
+    pub focal_length: f64,
+    pub stereo: Option<Stereo>,
+    // signed offset of this eye along the right axis, used per ray by
+    // omni-directional stereo panoramas
+    pub eye_offset: f64,
 }
 
-#[derive(Default)]
+// position and target are required, anything else left unset falls back
+// to a 640x360 image, a 90 degree vfov, +y up, 100 samples, a max depth of
+// 10, a pinhole lens focused on the target, a box filter, the aspect ratio
+// of the resolution, a perspective projection and no stereo rig
+#[derive(Debug, Default, Clone)]
 pub struct CameraBuilder {
-    image_width: Option<usize>,
-    image_height: Option<usize>,
-    vfov: Option<f64>,
-    position: Option<Vec3>,
-    target: Option<Vec3>,
-    up: Option<Vec3>,
-    samples: Option<usize>,
-    max_depth: Option<usize>,
+    pub(crate) image_width: Option<usize>,
+    pub(crate) image_height: Option<usize>,
+    pub(crate) vfov: Option<f64>,
+    pub(crate) position: Option<Vec3>,
+    pub(crate) target: Option<Vec3>,
+    pub(crate) up: Option<Vec3>,
+    pub(crate) samples: Option<usize>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) lens: Option<Lens>,
+    pub(crate) focus_distance: Option<f64>,
+    pub(crate) filter: Option<Filter>,
+    pub(crate) aspect_ratio: Option<f64>,
+    pub(crate) projection: Option<Projection>,
+    pub(crate) stereo: Option<Stereo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Lens {
+    Aperture(f64),
+    DefocusAngle(f64),
+}
+
+const DEFAULT_RESOLUTION: (usize, usize) = (640, 360);
+const DEFAULT_VFOV: f64 = 90.0;
+const DEFAULT_UP: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+const DEFAULT_SAMPLES: usize = 100;
+const DEFAULT_MAX_DEPTH: usize = 10;
+
+// why a builder could not produce a camera, fields are named after the
+// builder settings
+#[derive(Debug, Clone, PartialEq)]
+pub enum CameraError {
+    // a required setting was never given
+    Missing(&'static str),
+    ZeroResolution {
+        width: usize,
+        height: usize,
+    },
+    // a vector setting with a nan or infinite component
+    NonFinite(&'static str),
+    // a scalar setting outside the range where it means anything
+    OutOfRange {
+        field: &'static str,
+        value: f64,
+        expected: &'static str,
+    },
+    // position and target coincide so there is no view direction
+    NoViewDirection,
+    // the up vector is zero or parallel to the view direction
+    DegenerateUp,
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(field) => write!(f, "missing field '{field}'"),
+            Self::ZeroResolution { width, height } => {
+                write!(f, "resolution must be nonzero, got {width}x{height}")
+            }
+            Self::NonFinite(field) => write!(f, "{field} is not finite"),
+            Self::OutOfRange {
+                field,
+                value,
+                expected,
+            } => write!(f, "{field} must be {expected}, got {value}"),
+            Self::NoViewDirection => {
+                write!(f, "position and target are the same point")
+            }
+            Self::DegenerateUp => {
+                write!(f, "up is zero or parallel to the view direction")
+            }
+        }
+    }
+}
+
+impl Error for CameraError {}
+
+fn finite(field: &'static str, v: Vec3) -> Result<Vec3, CameraError> {
+    match v.x.is_finite() && v.y.is_finite() && v.z.is_finite() {
+        true => Ok(v),
+        false => Err(CameraError::NonFinite(field)),
+    }
+}
+
+fn in_range(
+    field: &'static str,
+    value: f64,
+    expected: &'static str,
+    valid: impl Fn(f64) -> bool,
+) -> Result<f64, CameraError> {
+    match value.is_finite() && valid(value) {
+        true => Ok(value),
+        false => Err(CameraError::OutOfRange {
+            field,
+            value,
+            expected,
+        }),
+    }
 }
 
 impl CameraBuilder {
@@ -59,56 +227,498 @@ impl CameraBuilder {
         self.max_depth = Some(depth);
         self
     }
-    pub fn build(&self) -> Camera {
-        let focal_length =
-            (self.target.unwrap() - self.position.unwrap()).length();
+    // lens diameter in world units, zero keeps a pinhole camera
+    pub fn aperture(&mut self, diameter: f64) -> &mut Self {
+        self.lens = Some(Lens::Aperture(diameter));
+        self
+    }
+    // cone angle in degrees subtended by the lens from the focus plane,
+    // an alternative to aperture that stays stable as focus changes
+    pub fn defocus_angle(&mut self, angle: f64) -> &mut Self {
+        self.lens = Some(Lens::DefocusAngle(angle));
+        self
+    }
+    // distance to the plane in perfect focus, defaults to the target
+    pub fn focus_distance(&mut self, distance: f64) -> &mut Self {
+        self.focus_distance = Some(distance);
+        self
+    }
+    pub fn filter(&mut self, filter: Filter) -> &mut Self {
+        self.filter = Some(filter);
+        self
+    }
+    // width over height of the viewport, defaults to the resolution's
+    pub fn aspect_ratio(&mut self, ratio: f64) -> &mut Self {
+        self.aspect_ratio = Some(ratio);
+        self
+    }
+    pub fn orthographic(&mut self, view_height: f64) -> &mut Self {
+        self.projection = Some(Projection::Orthographic {
+            height: view_height,
+        });
+        self
+    }
+    pub fn projection(&mut self, projection: Projection) -> &mut Self {
+        self.projection = Some(projection);
+        self
+    }
+    pub fn equirectangular(&mut self) -> &mut Self {
+        self.projection = Some(Projection::Equirectangular);
+        self
+    }
+    pub fn fisheye(&mut self, fov: f64) -> &mut Self {
+        self.projection = Some(Projection::Fisheye { fov });
+        self
+    }
+    pub fn cube_map(&mut self) -> &mut Self {
+        self.projection = Some(Projection::CubeMap);
+        self
+    }
+    pub fn stereo(
+        &mut self,
+        mode: StereoMode,
+        separation: f64,
+        convergence: f64,
+    ) -> &mut Self {
+        self.stereo = Some(Stereo {
+            mode,
+            separation,
+            convergence,
+        });
+        self
+    }
+    pub fn perspective(&mut self) -> &mut Self {
+        self.projection = Some(Projection::Perspective);
+        self
+    }
+    pub fn build(&self) -> Result<Camera, CameraError> {
+        let position = self.position.ok_or(CameraError::Missing("position"))?;
+        let target = self.target.ok_or(CameraError::Missing("target"))?;
+        let position = finite("position", position)?;
+        let target = finite("target", target)?;
+        let up = finite("up", self.up.unwrap_or(DEFAULT_UP))?;
 
-        let theta = self.vfov.unwrap() * std::f64::consts::PI / 180.0;
-        let h = (theta / 2.0).tan();
-        let vp_height = 2.0 * h * focal_length;
-        let vp_width = vp_height * 16.0 / 9.0;
-        let w =
-            Vec3::unit_vector(self.position.unwrap() - self.target.unwrap());
-        let u = Vec3::unit_vector(Vec3::cross(self.up.unwrap(), w));
+        let (width, height) = self
+            .image_width
+            .zip(self.image_height)
+            .unwrap_or(DEFAULT_RESOLUTION);
+        if width == 0 || height == 0 {
+            return Err(CameraError::ZeroResolution { width, height });
+        }
+        let samples = self.samples.unwrap_or(DEFAULT_SAMPLES);
+        if samples == 0 {
+            return Err(CameraError::OutOfRange {
+                field: "samples",
+                value: 0.0,
+                expected: "at least 1",
+            });
+        }
+        let max_depth = self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+        if max_depth == 0 {
+            return Err(CameraError::OutOfRange {
+                field: "max_depth",
+                value: 0.0,
+                expected: "at least 1",
+            });
+        }
+
+        let view = position - target;
+        if view.len_squared() < 1e-18 {
+            return Err(CameraError::NoViewDirection);
+        }
+        let w = Vec3::unit_vector(view);
+        let side = Vec3::cross(up, w);
+        if up.len_squared() == 0.0
+            || side.len_squared() < 1e-12 * up.len_squared()
+        {
+            return Err(CameraError::DegenerateUp);
+        }
+        let u = Vec3::unit_vector(side);
         let v = Vec3::cross(w, u);
 
+        let focal_length = match self.focus_distance {
+            Some(d) => in_range("focus_distance", d, "positive", |d| d > 0.0)?,
+            None => view.length(),
+        };
+        let aspect_ratio = match self.aspect_ratio {
+            Some(r) => in_range("aspect_ratio", r, "positive", |r| r > 0.0)?,
+            None => width as f64 / height as f64,
+        };
+        let projection = self.projection.unwrap_or_default();
+        // the faces are laid out 3 across and 2 down and must be square
+        if projection == Projection::CubeMap && width * 2 != height * 3 {
+            return Err(CameraError::OutOfRange {
+                field: "resolution",
+                value: width as f64 / height as f64,
+                expected: "a 3:2 grid of square faces for a cube map",
+            });
+        }
+
+        // orthographic viewports sit on the camera plane itself
+        let (vp_height, plane_distance) = match projection {
+            Projection::Perspective => {
+                let vfov = self.vfov.unwrap_or(DEFAULT_VFOV);
+                let vfov = in_range("vfov", vfov, "within (0, 180)", |a| {
+                    0.0 < a && a < 180.0
+                })?;
+                let h = (vfov.to_radians() / 2.0).tan();
+                (2.0 * h * focal_length, focal_length)
+            }
+            Projection::Orthographic { height } => {
+                let h = in_range("height", height, "positive", |h| h > 0.0)?;
+                (h, 0.0)
+            }
+            Projection::Fisheye { fov } => {
+                in_range("fov", fov, "within (0, 360]", |a| {
+                    0.0 < a && a <= 360.0
+                })?;
+                (1.0, focal_length)
+            }
+            // panoramic projections map pixels to directions directly
+            _ => (1.0, focal_length),
+        };
+        let vp_width = vp_height * aspect_ratio;
+
         let (viewport_u, viewport_v) = (vp_width * u, -vp_height * v);
 
-        let pixel_delta_u = viewport_u / self.image_width.unwrap() as f64;
-        let pixel_delta_v = viewport_v / self.image_height.unwrap() as f64;
+        let pixel_delta_u = viewport_u / width as f64;
+        let pixel_delta_v = viewport_v / height as f64;
 
-        let pixel_origin = self.position.unwrap()
-            - (focal_length * w)
+        let pixel_origin = position
+            - (plane_distance * w)
             - viewport_u / 2.0
             - viewport_v / 2.0
             + 0.5 * (pixel_delta_u + pixel_delta_v);
 
-        Camera {
-            position: self.position.unwrap(),
-            target: self.target.unwrap(),
-            image_width: self.image_width.unwrap(),
-            image_height: self.image_height.unwrap(),
+        let lens_radius = match self.lens {
+            Some(Lens::Aperture(diameter)) => {
+                let d = in_range("aperture", diameter, "non-negative", |d| {
+                    d >= 0.0
+                })?;
+                0.5 * d
+            }
+            Some(Lens::DefocusAngle(angle)) => {
+                let a =
+                    in_range("defocus_angle", angle, "within [0, 180)", |a| {
+                        (0.0..180.0).contains(&a)
+                    })?;
+                focal_length * (a.to_radians() / 2.0).tan()
+            }
+            None => 0.0,
+        };
+        let lens_radius = match projection {
+            Projection::Perspective => lens_radius,
+            _ => 0.0,
+        };
+
+        let filter = self.filter.unwrap_or_default();
+        match filter {
+            Filter::Gaussian { sigma } => {
+                in_range("sigma", sigma, "positive", |s| s > 0.0)?;
+            }
+            // the filter is sampled against its peak at the centre, which
+            // is only the largest value of |f| over this range
+            Filter::Mitchell { b, c } => {
+                let unit = |x: f64| (0.0..=1.0).contains(&x);
+                in_range("b", b, "within [0, 1]", unit)?;
+                in_range("c", c, "within [0, 1]", unit)?;
+            }
+            Filter::Box | Filter::Tent => {}
+        }
+        if let Some(stereo) = self.stereo {
+            let non_negative = |s: f64| s >= 0.0;
+            in_range(
+                "separation",
+                stereo.separation,
+                "non-negative",
+                non_negative,
+            )?;
+            in_range("convergence", stereo.convergence, "positive", |c| {
+                c > 0.0
+            })?;
+        }
+
+        Ok(Camera {
+            position,
+            target,
+            forward: -1.0 * w,
+            right: u,
+            up: v,
+            projection,
+            image_width: width,
+            image_height: height,
             pixel_delta_u,
             pixel_delta_v,
             pixel_origin,
-            samples: self.samples.unwrap(),
-            max_depth: self.max_depth.unwrap(),
-        }
+            defocus_disk_u: lens_radius * u,
+            defocus_disk_v: lens_radius * v,
+            samples,
+            max_depth,
+            filter,
+            focal_length,
+            stereo: self.stereo,
+            eye_offset: 0.0,
+        })
     }
 }
 
 impl Camera {
-    pub fn default() -> Self {
-        Camera {
-            position: Vec3::default(),
-            target: Vec3::default(),
-            pixel_delta_u: Vec3::default(),
-            pixel_delta_v: Vec3::default(),
-            pixel_origin: Vec3::default(),
-            image_width: 0,
-            image_height: 0,
-            samples: 0,
-            max_depth: 0,
+    // the camera seen from one eye of its stereo rig, equirectangular
+    // cameras offset each ray instead for omni-directional stereo
+    pub fn eye(&self, eye: Eye) -> Camera {
+        let Some(stereo) = self.stereo else {
+            return *self;
+        };
+        let offset = match eye {
+            Eye::Left => -0.5 * stereo.separation,
+            Eye::Right => 0.5 * stereo.separation,
+        };
+        let mut cam = *self;
+        if self.projection == Projection::Equirectangular {
+            cam.eye_offset = offset;
+            return cam;
+        }
+        let shift = offset * self.right;
+        cam.position += shift;
+        cam.pixel_origin += shift;
+        match stereo.mode {
+            StereoMode::Parallel => {
+                if self.projection == Projection::Perspective {
+                    cam.pixel_origin -=
+                        shift * (self.focal_length / stereo.convergence);
+                }
+            }
+            StereoMode::ToeIn => {
+                let angle = offset.atan2(stereo.convergence);
+                cam.rotate(angle);
+            }
+        }
+        cam
+    }
+
+    // turns the view about the up axis around the camera position
+    fn rotate(&mut self, angle: f64) {
+        let (axis, (sin, cos)) = (self.up, angle.sin_cos());
+        let turn = |v: Vec3| {
+            cos * v
+                + sin * Vec3::cross(axis, v)
+                + (1.0 - cos) * (axis * v) * axis
+        };
+        let relative = turn(self.pixel_origin - self.position);
+        self.pixel_origin = self.position + relative;
+        self.forward = turn(self.forward);
+        self.right = turn(self.right);
+        self.pixel_delta_u = turn(self.pixel_delta_u);
+        self.pixel_delta_v = turn(self.pixel_delta_v);
+        self.defocus_disk_u = turn(self.defocus_disk_u);
+        self.defocus_disk_v = turn(self.defocus_disk_v);
+    }
+
+    // ray through row i and column j of the image, offset by a fraction
+    // of a pixel, or None where the projection covers no direction
+    pub fn get_ray(&self, i: usize, j: usize, dx: f64, dy: f64) -> Option<Ray> {
+        let (row, col) = (i as f64 + dy, j as f64 + dx);
+        let (width, height) =
+            (self.image_width as f64, self.image_height as f64);
+        let direction = match self.projection {
+            Projection::Perspective => return Some(self.lens_ray(row, col)),
+            Projection::Orthographic { .. } => {
+                let pixel = self.pixel_at(row, col);
+                return Some(Ray::new(pixel, self.forward));
+            }
+            Projection::Equirectangular => {
+                let longitude = ((col + 0.5) / width - 0.5) * 2.0 * PI;
+                let latitude = (0.5 - (row + 0.5) / height) * PI;
+                Vec3::new(
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    -latitude.cos() * longitude.cos(),
+                )
+            }
+            Projection::Fisheye { fov } => {
+                let radius = 0.5 * width.min(height);
+                let x = (col + 0.5 - 0.5 * width) / radius;
+                let y = (0.5 * height - row - 0.5) / radius;
+                let r = (x * x + y * y).sqrt();
+                if r > 1.0 {
+                    return None;
+                }
+                let theta = r * fov.to_radians() / 2.0;
+                match r > 0.0 {
+                    true => Vec3::new(
+                        theta.sin() * x / r,
+                        theta.sin() * y / r,
+                        -theta.cos(),
+                    ),
+                    false => Vec3::new(0.0, 0.0, -1.0),
+                }
+            }
+            Projection::CubeMap => {
+                let (face_w, face_h) = (width / 3.0, height / 2.0);
+                let (x, y) = (col + 0.5, row + 0.5);
+                let cell_x = ((x / face_w) as usize).min(2);
+                let cell_y = ((y / face_h) as usize).min(1);
+                let a = 2.0 * (x / face_w - cell_x as f64) - 1.0;
+                let b = 1.0 - 2.0 * (y / face_h - cell_y as f64);
+                let (look, up) = CUBE_FACES[3 * cell_y + cell_x];
+                look + a * Vec3::cross(look, up) + b * up
+            }
+        };
+        let (origin, direction) = self.eye_ray(direction);
+        Some(Ray::new(origin, self.world_direction(direction)))
+    }
+
+    // omni-directional stereo moves the origin onto the eye circle
+    // tangent to the horizontal view direction
+    fn eye_ray(&self, direction: Vec3) -> (Vec3, Vec3) {
+        let Some(stereo) = self.stereo.filter(|_| self.eye_offset != 0.0)
+        else {
+            return (self.position, direction);
+        };
+        let longitude = direction.x.atan2(-direction.z);
+        let local = Vec3::new(longitude.cos(), 0.0, longitude.sin());
+        let offset = self.eye_offset * local;
+        let origin = self.position + self.world_direction(offset);
+        match stereo.mode {
+            StereoMode::Parallel => (origin, direction),
+            StereoMode::ToeIn => {
+                (origin, stereo.convergence * direction - offset)
+            }
+        }
+    }
+
+    fn pixel_at(&self, row: f64, col: f64) -> Vec3 {
+        self.pixel_origin
+            + (self.pixel_delta_v * row)
+            + (self.pixel_delta_u * col)
+    }
+
+    // leaves from a random point on the lens disk and passes through the
+    // pixel position on the focus plane
+    fn lens_ray(&self, row: f64, col: f64) -> Ray {
+        let p = Vec3::random_in_unit_disk();
+        let origin = self.position
+            + p.x * self.defocus_disk_u
+            + p.y * self.defocus_disk_v;
+        Ray::new(origin, self.pixel_at(row, col) - origin)
+    }
+
+    fn world_direction(&self, local: Vec3) -> Vec3 {
+        local.x * self.right + local.y * self.up - local.z * self.forward
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> CameraBuilder {
+        let mut builder = CameraBuilder::new();
+        builder.position(0.0, 1.0, 3.0).target(0.0, 0.0, 0.0);
+        builder
+    }
+
+    fn error(builder: &mut CameraBuilder) -> CameraError {
+        builder.build().unwrap_err()
+    }
+
+    #[test]
+    fn position_and_target_are_required() {
+        let err = error(CameraBuilder::new().target(0.0, 0.0, 0.0));
+        assert_eq!(err, CameraError::Missing("position"));
+        let err = error(CameraBuilder::new().position(0.0, 0.0, 1.0));
+        assert_eq!(err, CameraError::Missing("target"));
+        assert_eq!(err.to_string(), "missing field 'target'");
+    }
+
+    #[test]
+    fn resolutions_must_be_nonzero() {
+        let err = error(builder().resolution(0, 360));
+        let expected = CameraError::ZeroResolution {
+            width: 0,
+            height: 360,
+        };
+        assert_eq!(err, expected);
+        let err = error(builder().resolution(640, 0));
+        assert!(matches!(err, CameraError::ZeroResolution { height: 0, .. }));
+    }
+
+    #[test]
+    fn vectors_must_be_finite() {
+        let err = error(builder().position(f64::NAN, 0.0, 0.0));
+        assert_eq!(err, CameraError::NonFinite("position"));
+        let err = error(builder().upward(0.0, f64::INFINITY, 0.0));
+        assert_eq!(err, CameraError::NonFinite("up"));
+    }
+
+    #[test]
+    fn scalars_must_be_in_range() {
+        let out_of_range = |builder: &mut CameraBuilder| match error(builder) {
+            CameraError::OutOfRange { field, .. } => field,
+            err => panic!("expected OutOfRange, got {err:?}"),
+        };
+        assert_eq!(out_of_range(builder().samples(0)), "samples");
+        assert_eq!(out_of_range(builder().max_depth(0)), "max_depth");
+        assert_eq!(out_of_range(builder().vfov(180.0)), "vfov");
+        assert_eq!(out_of_range(builder().vfov(f64::NAN)), "vfov");
+        assert_eq!(out_of_range(builder().aperture(-1.0)), "aperture");
+        assert_eq!(out_of_range(builder().fisheye(0.0)), "fov");
+        assert_eq!(out_of_range(builder().orthographic(0.0)), "height");
+        assert_eq!(out_of_range(builder().aspect_ratio(0.0)), "aspect_ratio");
+        let gaussian = Filter::Gaussian { sigma: 0.0 };
+        assert_eq!(out_of_range(builder().filter(gaussian)), "sigma");
+        let mut stereo = builder();
+        stereo.stereo(StereoMode::Parallel, 0.1, 0.0);
+        assert_eq!(out_of_range(&mut stereo), "convergence");
+        let err = error(builder().samples(0));
+        assert_eq!(err.to_string(), "samples must be at least 1, got 0");
+    }
+
+    #[test]
+    fn the_view_needs_a_direction_and_an_up() {
+        let err = error(builder().position(0.0, 0.0, 0.0));
+        assert_eq!(err, CameraError::NoViewDirection);
+        // looking straight down along the default up
+        let err = error(builder().position(0.0, 5.0, 0.0));
+        assert_eq!(err, CameraError::DegenerateUp);
+        let err = error(builder().upward(0.0, 0.0, 0.0));
+        assert_eq!(err, CameraError::DegenerateUp);
+    }
+
+    #[test]
+    fn mitchell_parameters_stay_in_the_unit_square() {
+        for (b, c) in [(3.0, 0.0), (0.0, -0.5), (0.5, f64::NAN)] {
+            let filter = Filter::Mitchell { b, c };
+            let err = builder().filter(filter).build().unwrap_err();
+            assert!(matches!(err, CameraError::OutOfRange { .. }), "{err}");
+        }
+        for (b, c) in [(0.0, 0.0), (1.0, 1.0), (1.0 / 3.0, 1.0 / 3.0)] {
+            builder().filter(Filter::Mitchell { b, c }).build().unwrap();
+        }
+    }
+
+    #[test]
+    fn cube_maps_need_square_faces() {
+        for (width, height) in [(10, 10), (301, 200), (300, 201)] {
+            let err = builder()
+                .cube_map()
+                .resolution(width, height)
+                .build()
+                .unwrap_err();
+            assert!(
+                matches!(err, CameraError::OutOfRange { field, .. }
+                    if field == "resolution"),
+                "{width}x{height}: {err}"
+            );
+        }
+        let camera = builder().cube_map().resolution(12, 8).build().unwrap();
+        // the centre of every face looks straight down its axis
+        for (face, (look, _)) in CUBE_FACES.iter().enumerate() {
+            let (i, j) = (4 * (face / 3) + 2, 4 * (face % 3) + 2);
+            let ray = camera.get_ray(i, j, -0.5, -0.5).unwrap();
+            let world = camera.world_direction(*look);
+            let direction = Vec3::unit_vector(ray.direction);
+            assert!((direction - world).length() < 1e-12, "face {face}");
         }
     }
 }
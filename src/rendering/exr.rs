@@ -0,0 +1,425 @@
+use std::io;
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::math::Vec3;
+
+use super::hdr::invalid;
+use super::Image;
+use super::Pixel;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+// zip compression packs this many scanlines into each chunk
+const ZIP_LINES: usize = 16;
+// deflate never expands data by more than about 1032:1, which bounds how
+// many pixels a file of a given size can describe
+const MAX_ZIP_RATIO: usize = 1032;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum ExrPixelType {
+    #[default]
+    Half,
+    Float,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum ExrCompression {
+    None,
+    #[default]
+    Zip,
+}
+
+impl ExrPixelType {
+    fn size(self) -> usize {
+        match self {
+            Self::Half => 2,
+            Self::Float => 4,
+        }
+    }
+
+    fn code(self) -> i32 {
+        match self {
+            Self::Half => 1,
+            Self::Float => 2,
+        }
+    }
+}
+
+impl ExrCompression {
+    fn lines(self) -> usize {
+        match self {
+            Self::None => 1,
+            Self::Zip => ZIP_LINES,
+        }
+    }
+}
+
+// ieee half precision with round to nearest even
+fn f32_to_half(v: f32) -> u16 {
+    let bits = v.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mant = bits & 0x7f_ffff;
+    if exp == 0xff {
+        let nan = if mant != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        return sign | 0x7c00;
+    }
+    let (half, rem, halfway) = match e <= 0 {
+        // subnormal, the implicit leading bit becomes explicit
+        true => {
+            if e < -10 {
+                return sign;
+            }
+            let m = mant | 0x80_0000;
+            let shift = (14 - e) as u32;
+            (m >> shift, m & ((1 << shift) - 1), 1 << (shift - 1))
+        }
+        false => (((e as u32) << 10) | (mant >> 13), mant & 0x1fff, 0x1000),
+    };
+    // a carry out of the mantissa correctly bumps the exponent
+    let round = rem > halfway || (rem == halfway && half & 1 == 1);
+    sign | (half + round as u32) as u16
+}
+
+fn half_to_f32(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exp = ((h >> 10) & 0x1f) as u32;
+    let mant = (h & 0x3ff) as u32;
+    match exp {
+        0 => {
+            let value = mant as f32 * 2f32.powi(-24);
+            f32::from_bits(sign | value.to_bits())
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mant << 13)),
+        _ => f32::from_bits(sign | ((exp + 112) << 23) | (mant << 13)),
+    }
+}
+
+fn attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(kind.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+fn header(image: &Image, pixel: ExrPixelType, zip: ExrCompression) -> Vec<u8> {
+    let mut channels = Vec::new();
+    // channels are stored in alphabetical order
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&pixel.code().to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    let window: Vec<u8> = [0, 0, image.cols as i32 - 1, image.rows as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let compression = match zip {
+        ExrCompression::None => 0u8,
+        ExrCompression::Zip => 3u8,
+    };
+    let mut out = Vec::new();
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&2u32.to_le_bytes());
+    attribute(&mut out, "channels", "chlist", &channels);
+    attribute(&mut out, "compression", "compression", &[compression]);
+    attribute(&mut out, "dataWindow", "box2i", &window);
+    attribute(&mut out, "displayWindow", "box2i", &window);
+    attribute(&mut out, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut out, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    let center = [0f32.to_le_bytes(), 0f32.to_le_bytes()].concat();
+    attribute(&mut out, "screenWindowCenter", "v2f", &center);
+    attribute(&mut out, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    out.push(0);
+    out
+}
+
+// the zip predictor splits even and odd bytes and stores differences
+fn zip_encode(raw: &[u8]) -> io::Result<Vec<u8>> {
+    let half = raw.len().div_ceil(2);
+    let mut tmp = vec![0u8; raw.len()];
+    for (i, &b) in raw.iter().enumerate() {
+        let idx = if i % 2 == 0 { i / 2 } else { half + i / 2 };
+        tmp[idx] = b;
+    }
+    for t in (1..tmp.len()).rev() {
+        tmp[t] = tmp[t].wrapping_sub(tmp[t - 1]).wrapping_add(128);
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&tmp)?;
+    encoder.finish()
+}
+
+fn zip_decode(data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let mut tmp = Vec::with_capacity(size);
+    // one byte past the expected size is enough to tell it is wrong
+    let limit = size as u64 + 1;
+    ZlibDecoder::new(data).take(limit).read_to_end(&mut tmp)?;
+    if tmp.len() != size {
+        return Err(invalid("zip chunk has the wrong size"));
+    }
+    for t in 1..tmp.len() {
+        tmp[t] = tmp[t - 1].wrapping_add(tmp[t]).wrapping_sub(128);
+    }
+    let half = size.div_ceil(2);
+    let raw = (0..size)
+        .map(|i| match i % 2 {
+            0 => tmp[i / 2],
+            _ => tmp[half + i / 2],
+        })
+        .collect();
+    Ok(raw)
+}
+
+// single part scanline file with B, G and R channels
+pub fn write_exr<W: Write>(
+    mut out: W,
+    image: &Image,
+    pixel: ExrPixelType,
+    compression: ExrCompression,
+) -> io::Result<()> {
+    let header = header(image, pixel, compression);
+    let lines = compression.lines();
+    let chunk_count = image.rows.div_ceil(lines);
+    let mut chunks = Vec::with_capacity(chunk_count);
+    for chunk in 0..chunk_count {
+        let first = chunk * lines;
+        let mut raw = Vec::new();
+        for i in first..(first + lines).min(image.rows) {
+            let row = &image.data[i * image.cols..][..image.cols];
+            let channels: [fn(Vec3) -> f64; 3] = [|c| c.z, |c| c.y, |c| c.x];
+            for channel in channels {
+                for p in row {
+                    let v = channel(p.color()) as f32;
+                    match pixel {
+                        ExrPixelType::Half => {
+                            raw.extend_from_slice(&f32_to_half(v).to_le_bytes())
+                        }
+                        ExrPixelType::Float => {
+                            raw.extend_from_slice(&v.to_le_bytes())
+                        }
+                    }
+                }
+            }
+        }
+        // chunks that do not shrink are stored raw
+        let data = match compression {
+            ExrCompression::Zip => {
+                let packed = zip_encode(&raw)?;
+                if packed.len() < raw.len() {
+                    packed
+                } else {
+                    raw
+                }
+            }
+            ExrCompression::None => raw,
+        };
+        chunks.push((first as i32, data));
+    }
+    let mut offset = (header.len() + 8 * chunk_count) as u64;
+    out.write_all(&header)?;
+    for (_, data) in &chunks {
+        out.write_all(&offset.to_le_bytes())?;
+        offset += 8 + data.len() as u64;
+    }
+    for (y, data) in &chunks {
+        out.write_all(&y.to_le_bytes())?;
+        out.write_all(&(data.len() as i32).to_le_bytes())?;
+        out.write_all(data)?;
+    }
+    out.flush()
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let slice = self
+            .pos
+            .checked_add(n)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| invalid("exr data truncated"))?;
+        let end = self.pos + n;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn string(&mut self) -> io::Result<&'a str> {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| invalid("unterminated exr string"))?;
+        self.pos += len + 1;
+        std::str::from_utf8(&rest[..len])
+            .map_err(|_| invalid("exr string is not utf8"))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        let b = self.bytes(4)?;
+        Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let b = self.bytes(8)?;
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(b);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+// reads uncompressed and zip compressed scanline files, channels other
+// than R, G and B are skipped and missing ones read as zero
+pub fn read_exr(data: &[u8]) -> io::Result<Image> {
+    let mut cur = Cursor { data, pos: 0 };
+    if cur.bytes(4)? != MAGIC {
+        return Err(invalid("not an exr file"));
+    }
+    let version = cur.i32()?;
+    if version & 0xff != 2 || version & 0x1e00 != 0 {
+        return Err(invalid("only single part scanline exr is supported"));
+    }
+    let mut channels: Vec<(String, ExrPixelType)> = Vec::new();
+    let (mut window, mut compression) = (None, ExrCompression::None);
+    loop {
+        let name = cur.string()?;
+        if name.is_empty() {
+            break;
+        }
+        let _kind = cur.string()?;
+        let size = cur.u32()? as usize;
+        let mut value = Cursor {
+            data: cur.bytes(size)?,
+            pos: 0,
+        };
+        match name {
+            "channels" => loop {
+                let channel = value.string()?;
+                if channel.is_empty() {
+                    break;
+                }
+                let pixel = match value.i32()? {
+                    1 => ExrPixelType::Half,
+                    2 => ExrPixelType::Float,
+                    _ => return Err(invalid("unsupported exr pixel type")),
+                };
+                value.bytes(12)?;
+                channels.push((channel.to_string(), pixel));
+            },
+            "compression" => {
+                compression = match value.bytes(1)?[0] {
+                    0 => ExrCompression::None,
+                    3 => ExrCompression::Zip,
+                    other => {
+                        let msg =
+                            format!("unsupported exr compression {other}");
+                        return Err(invalid(msg));
+                    }
+                }
+            }
+            "dataWindow" => {
+                let b =
+                    [value.i32()?, value.i32()?, value.i32()?, value.i32()?];
+                window = Some(b);
+            }
+            _ => {}
+        }
+    }
+    let Some([x_min, y_min, x_max, y_max]) = window else {
+        return Err(invalid("exr file has no data window"));
+    };
+    let extent = |min: i32, max: i32| (max as i64 - min as i64 + 1).max(0);
+    let width = usize::try_from(extent(x_min, x_max)).unwrap_or(usize::MAX);
+    let height = usize::try_from(extent(y_min, y_max)).unwrap_or(usize::MAX);
+    if width == 0 || height == 0 {
+        return Err(invalid("exr data window is empty"));
+    }
+    let pixel_size: usize = channels.iter().map(|(_, p)| p.size()).sum();
+    let line_size = width
+        .checked_mul(pixel_size)
+        .ok_or_else(|| invalid("exr dimensions are too large"))?;
+    let fits = line_size.checked_mul(height).is_some_and(|size| {
+        let remaining = data.len() - cur.pos;
+        match compression {
+            ExrCompression::None => size <= remaining,
+            ExrCompression::Zip => size / MAX_ZIP_RATIO <= remaining,
+        }
+    });
+    if !fits || width.checked_mul(height).is_none() {
+        return Err(invalid("exr data truncated"));
+    }
+    let lines = compression.lines();
+    let chunk_count = height.div_ceil(lines);
+    let offsets = (0..chunk_count)
+        .map(|_| cur.u64())
+        .collect::<io::Result<Vec<u64>>>()?;
+    let mut colors = vec![Vec3::default(); width * height];
+    for offset in offsets {
+        let mut chunk = Cursor {
+            data,
+            pos: usize::try_from(offset).unwrap_or(usize::MAX),
+        };
+        let first = chunk.i32()? as i64 - y_min as i64;
+        let first = usize::try_from(first)
+            .ok()
+            .filter(|&first| first < height)
+            .ok_or_else(|| invalid("exr chunk is outside the data window"))?;
+        let size = chunk.u32()? as usize;
+        let count = lines.min(height.saturating_sub(first));
+        let expected = line_size * count;
+        let packed = chunk.bytes(size)?;
+        let raw = match size < expected {
+            true => zip_decode(packed, expected)?,
+            false => packed.to_vec(),
+        };
+        let mut pixels = Cursor { data: &raw, pos: 0 };
+        for i in first..first + count {
+            for (name, pixel) in &channels {
+                let values = pixels.bytes(pixel.size() * width)?;
+                let row = &mut colors[i * width..][..width];
+                for (c, b) in
+                    row.iter_mut().zip(values.chunks_exact(pixel.size()))
+                {
+                    let v = match pixel {
+                        ExrPixelType::Half => {
+                            half_to_f32(u16::from_le_bytes([b[0], b[1]]))
+                        }
+                        ExrPixelType::Float => {
+                            f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+                        }
+                    } as f64;
+                    match name.as_str() {
+                        "R" => c.x = v,
+                        "G" => c.y = v,
+                        "B" => c.z = v,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    let mut image = Image::new(width, height);
+    image.data = colors.into_iter().map(Pixel::from).collect();
+    Ok(image)
+}
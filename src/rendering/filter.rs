@@ -0,0 +1,165 @@
+use crate::math::random_double;
+
+// default parameters of Filter::gaussian and Filter::mitchell, the usual
+// B = C = 1/3 recommended by Mitchell and Netravali
+pub const GAUSSIAN_SIGMA: f64 = 0.5;
+pub const MITCHELL_B: f64 = 1.0 / 3.0;
+pub const MITCHELL_C: f64 = 1.0 / 3.0;
+
+// pixel reconstruction filter, each pixel averages its own samples
+// placed around the pixel centre according to the filter shape
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Filter {
+    #[default]
+    Box,
+    Tent,
+    Gaussian {
+        sigma: f64,
+    },
+    Mitchell {
+        b: f64,
+        c: f64,
+    },
+}
+
+impl Filter {
+    pub fn mitchell() -> Self {
+        Self::Mitchell {
+            b: MITCHELL_B,
+            c: MITCHELL_C,
+        }
+    }
+
+    pub fn gaussian() -> Self {
+        Self::Gaussian {
+            sigma: GAUSSIAN_SIGMA,
+        }
+    }
+
+    // half width of the support in pixels
+    pub fn radius(&self) -> f64 {
+        match self {
+            Self::Box => 0.5,
+            Self::Tent => 1.0,
+            Self::Gaussian { sigma } => 3.0 * sigma,
+            Self::Mitchell { .. } => 2.0,
+        }
+    }
+
+    // random offset from the pixel centre distributed like the magnitude
+    // of the filter, with the sign of the filter as the sample weight
+    pub fn sample(&self) -> (f64, f64, f64) {
+        let (dx, sx) = self.sample_1d();
+        let (dy, sy) = self.sample_1d();
+        (dx, dy, sx * sy)
+    }
+
+    fn sample_1d(&self) -> (f64, f64) {
+        let r = self.radius();
+        match *self {
+            Self::Box => ((2.0 * random_double() - 1.0) * r, 1.0),
+            Self::Tent => {
+                let u = 2.0 * random_double() - 1.0;
+                (u.signum() * (1.0 - (1.0 - u.abs()).sqrt()), 1.0)
+            }
+            // box muller, rejecting the tails past the support
+            Self::Gaussian { sigma } => loop {
+                let (u1, u2) = (1.0 - random_double(), random_double());
+                let x = sigma
+                    * (-2.0 * u1.ln()).sqrt()
+                    * (2.0 * std::f64::consts::PI * u2).cos();
+                if x.abs() < r {
+                    return (x, 1.0);
+                }
+            },
+            // rejection against the peak at the centre
+            Self::Mitchell { b, c } => {
+                let peak = Self::mitchell_1d(0.0, b, c).abs();
+                loop {
+                    let x = (2.0 * random_double() - 1.0) * r;
+                    let f = Self::mitchell_1d(x, b, c);
+                    if random_double() * peak < f.abs() {
+                        return (x, f.signum());
+                    }
+                }
+            }
+        }
+    }
+
+    fn mitchell_1d(x: f64, b: f64, c: f64) -> f64 {
+        let x = x.abs();
+        match x {
+            x if x < 1.0 => {
+                ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                    + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                    + (6.0 - 2.0 * b))
+                    / 6.0
+            }
+            x if x < 2.0 => {
+                ((-b - 6.0 * c) * x * x * x
+                    + (6.0 * b + 30.0 * c) * x * x
+                    + (-12.0 * b - 48.0 * c) * x
+                    + (8.0 * b + 24.0 * c))
+                    / 6.0
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::seed_rng;
+
+    #[test]
+    fn mitchell_peaks_at_the_centre_over_the_valid_range() {
+        for i in 0..=10 {
+            for j in 0..=10 {
+                let (b, c) = (i as f64 / 10.0, j as f64 / 10.0);
+                let peak = Filter::mitchell_1d(0.0, b, c);
+                for k in 1..=400 {
+                    let f = Filter::mitchell_1d(k as f64 / 100.0, b, c);
+                    assert!(f.abs() <= peak, "b = {b}, c = {c}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mitchell_samples_follow_the_filter_magnitude() {
+        const BINS: usize = 40;
+        const SAMPLES: usize = 200_000;
+        seed_rng(3);
+        for (b, c) in [(MITCHELL_B, MITCHELL_C), (0.0, 1.0), (1.0, 0.0)] {
+            let filter = Filter::Mitchell { b, c };
+            let bin = |x: f64| ((x + 2.0) / 4.0 * BINS as f64) as usize;
+            let mut counts = [0usize; BINS];
+            for _ in 0..SAMPLES {
+                let (x, sign) = filter.sample_1d();
+                assert_eq!(sign, Filter::mitchell_1d(x, b, c).signum());
+                counts[bin(x).min(BINS - 1)] += 1;
+            }
+            // integral of |f| over each bin by the midpoint rule
+            let steps = 100;
+            let width = 4.0 / (BINS * steps) as f64;
+            let mass: Vec<f64> = (0..BINS)
+                .map(|i| {
+                    (0..steps)
+                        .map(|k| -2.0 + ((i * steps + k) as f64 + 0.5) * width)
+                        .map(|x| Filter::mitchell_1d(x, b, c).abs() * width)
+                        .sum()
+                })
+                .collect();
+            let total: f64 = mass.iter().sum();
+            for (count, mass) in counts.iter().zip(&mass) {
+                let observed = *count as f64 / SAMPLES as f64;
+                let expected = mass / total;
+                assert!(
+                    (observed - expected).abs() < 3e-3,
+                    "b = {b}, c = {c}: {observed} vs {expected}"
+                );
+            }
+        }
+    }
+}
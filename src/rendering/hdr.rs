@@ -0,0 +1,296 @@
+use std::io;
+use std::io::Write;
+
+use crate::math::Vec3;
+
+use super::Image;
+use super::Pixel;
+
+pub(super) fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// splits off the next whitespace separated token of a text header
+fn token<'a>(data: &mut &'a [u8]) -> io::Result<&'a str> {
+    let start = data
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .ok_or_else(|| invalid("unexpected end of header"))?;
+    let rest = &data[start..];
+    let len = rest
+        .iter()
+        .position(|b| b.is_ascii_whitespace())
+        .unwrap_or(rest.len());
+    let (word, tail) = rest.split_at(len);
+    // exactly one whitespace byte separates the header from the data
+    *data = tail.get(1..).unwrap_or(&[]);
+    std::str::from_utf8(word).map_err(|_| invalid("header is not ascii"))
+}
+
+fn number<T: std::str::FromStr>(data: &mut &[u8]) -> io::Result<T> {
+    let word = token(data)?;
+    word.parse()
+        .map_err(|_| invalid(format!("expected a number, found {word}")))
+}
+
+// little endian portable float map, rows are stored bottom to top
+pub fn write_pfm<W: Write>(mut out: W, image: &Image) -> io::Result<()> {
+    write!(out, "PF\n{} {}\n-1.0\n", image.cols, image.rows)?;
+    let mut row = Vec::with_capacity(12 * image.cols);
+    for i in (0..image.rows).rev() {
+        row.clear();
+        for j in 0..image.cols {
+            let c = image.get(i, j).color();
+            for x in [c.x, c.y, c.z] {
+                row.extend_from_slice(&(x as f32).to_le_bytes());
+            }
+        }
+        out.write_all(&row)?;
+    }
+    out.flush()
+}
+
+pub fn read_pfm(data: &[u8]) -> io::Result<Image> {
+    let mut rest = data;
+    let channels = match token(&mut rest)? {
+        "PF" => 3,
+        "Pf" => 1,
+        magic => return Err(invalid(format!("not a pfm file: {magic}"))),
+    };
+    let width: usize = number(&mut rest)?;
+    let height: usize = number(&mut rest)?;
+    let scale: f64 = number(&mut rest)?;
+    if width == 0 || height == 0 {
+        return Err(invalid("pfm image is empty"));
+    }
+    let little_endian = scale < 0.0;
+    let expected = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(4 * channels))
+        .ok_or_else(|| invalid("pfm dimensions are too large"))?;
+    if rest.len() < expected {
+        return Err(invalid("pfm data is truncated"));
+    }
+    let floats: Vec<f64> = rest[..expected]
+        .chunks_exact(4)
+        .map(|b| {
+            let bytes = [b[0], b[1], b[2], b[3]];
+            match little_endian {
+                true => f32::from_le_bytes(bytes) as f64,
+                false => f32::from_be_bytes(bytes) as f64,
+            }
+        })
+        .collect();
+    let mut image = Image::new(width, height);
+    for i in (0..height).rev() {
+        for j in 0..width {
+            let p = &floats[channels * (i * width + j)..][..channels];
+            let color = match channels {
+                3 => Vec3::new(p[0], p[1], p[2]),
+                _ => Vec3::new(p[0], p[0], p[0]),
+            };
+            image.data.push(Pixel::from(color));
+        }
+    }
+    Ok(image)
+}
+
+// shared 8 bit mantissa with an 8 bit exponent, as in Greg Ward's format
+fn to_rgbe(c: Vec3) -> [u8; 4] {
+    let v = c.x.max(c.y).max(c.z);
+    if v < 1e-32 {
+        return [0; 4];
+    }
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / 2f64.powi(e) >= 1.0 {
+        e += 1;
+    }
+    let scale = 256.0 / 2f64.powi(e);
+    let byte = |x: f64| (x.max(0.0) * scale).min(255.0) as u8;
+    [byte(c.x), byte(c.y), byte(c.z), (e + 128) as u8]
+}
+
+fn from_rgbe(rgbe: [u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::default();
+    }
+    let f = 2f64.powi(rgbe[3] as i32 - 136);
+    let channel = |b: u8| b as f64 * f;
+    Vec3::new(channel(rgbe[0]), channel(rgbe[1]), channel(rgbe[2]))
+}
+
+// run length encoding of one component of a scanline, runs of four or
+// more equal bytes are stored as a count above 128 and the byte
+fn encode_runs(data: &[u8], out: &mut Vec<u8>) {
+    let n = data.len();
+    let mut cur = 0;
+    while cur < n {
+        let (mut beg_run, mut run_count, mut old_run_count) = (cur, 0, 0);
+        while run_count < 4 && beg_run < n {
+            beg_run += run_count;
+            old_run_count = run_count;
+            run_count = 1;
+            while beg_run + run_count < n
+                && run_count < 127
+                && data[beg_run] == data[beg_run + run_count]
+            {
+                run_count += 1;
+            }
+        }
+        if old_run_count > 1 && old_run_count == beg_run - cur {
+            out.extend_from_slice(&[128 + old_run_count as u8, data[cur]]);
+            cur = beg_run;
+        }
+        while cur < beg_run {
+            let count = (beg_run - cur).min(128);
+            out.push(count as u8);
+            out.extend_from_slice(&data[cur..cur + count]);
+            cur += count;
+        }
+        if run_count >= 4 {
+            out.extend_from_slice(&[128 + run_count as u8, data[beg_run]]);
+            cur += run_count;
+        }
+    }
+}
+
+// radiance rgbe with adaptive run length encoded scanlines
+pub fn write_hdr<W: Write>(mut out: W, image: &Image) -> io::Result<()> {
+    write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n")?;
+    writeln!(out, "-Y {} +X {}", image.rows, image.cols)?;
+    let width = image.cols;
+    let mut components = vec![0u8; 4 * width];
+    let mut line = Vec::with_capacity(5 * width);
+    for i in 0..image.rows {
+        line.clear();
+        for j in 0..width {
+            let rgbe = to_rgbe(image.get(i, j).color());
+            for (k, b) in rgbe.into_iter().enumerate() {
+                components[k * width + j] = b;
+            }
+        }
+        // the run length encoding only covers widths of 8 to 32767
+        match (8..32768).contains(&width) {
+            true => {
+                line.extend_from_slice(&[2, 2, (width >> 8) as u8]);
+                line.push((width & 0xff) as u8);
+                for component in components.chunks_exact(width) {
+                    encode_runs(component, &mut line);
+                }
+            }
+            false => {
+                for j in 0..width {
+                    line.extend((0..4).map(|k| components[k * width + j]));
+                }
+            }
+        }
+        out.write_all(&line)?;
+    }
+    out.flush()
+}
+
+pub fn read_hdr(data: &[u8]) -> io::Result<Image> {
+    let mut rest = data;
+    let mut line = || -> io::Result<&str> {
+        let end = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("unexpected end of header"))?;
+        let text = std::str::from_utf8(&rest[..end])
+            .map_err(|_| invalid("header is not ascii"))?;
+        rest = &rest[end + 1..];
+        Ok(text)
+    };
+    let magic = line()?;
+    if !magic.starts_with("#?") {
+        return Err(invalid(format!("not a radiance file: {magic}")));
+    }
+    loop {
+        let text = line()?;
+        if text.is_empty() {
+            break;
+        }
+        if let Some(format) = text.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid(format!("unsupported format {format}")));
+            }
+        }
+    }
+    let resolution: Vec<&str> = line()?.split_whitespace().collect();
+    let (height, width): (Option<usize>, Option<usize>) = match resolution[..] {
+        ["-Y", h, "+X", w] => (h.parse().ok(), w.parse().ok()),
+        _ => (None, None),
+    };
+    let (Some(height), Some(width)) = (height, width) else {
+        return Err(invalid("only -Y h +X w orientation is supported"));
+    };
+    if width == 0 || height == 0 {
+        return Err(invalid("hdr image is empty"));
+    }
+    // the most compact scanline is all runs of 127, so anything claiming
+    // more pixels than the data could hold is rejected before allocating
+    let min_line = match (8..32768).contains(&width) {
+        true => 4 + 4 * 2 * width.div_ceil(127),
+        false => width.saturating_mul(4),
+    };
+    let fits = height
+        .checked_mul(min_line)
+        .is_some_and(|size| size <= rest.len());
+    if !fits {
+        return Err(invalid("hdr data truncated"));
+    }
+    let mut image = Image::new(width, height);
+    let mut components = vec![0u8; 4 * width];
+    let mut pos = 0;
+    let mut byte = || -> io::Result<u8> {
+        let b = *rest.get(pos).ok_or_else(|| invalid("hdr data truncated"))?;
+        pos += 1;
+        Ok(b)
+    };
+    for _ in 0..height {
+        let head = [byte()?, byte()?, byte()?, byte()?];
+        let encoded = (8..32768).contains(&width)
+            && head[0] == 2
+            && head[1] == 2
+            && head[2] & 0x80 == 0;
+        if !encoded {
+            // flat scanline, the header already holds the first pixel
+            image.data.push(Pixel::from(from_rgbe(head)));
+            for _ in 1..width {
+                let rgbe = [byte()?, byte()?, byte()?, byte()?];
+                image.data.push(Pixel::from(from_rgbe(rgbe)));
+            }
+            continue;
+        }
+        if ((head[2] as usize) << 8 | head[3] as usize) != width {
+            return Err(invalid("scanline width mismatch"));
+        }
+        for component in components.chunks_exact_mut(width) {
+            let mut j = 0;
+            while j < width {
+                let count = byte()? as usize;
+                let (run, count) = match count > 128 {
+                    true => (true, count - 128),
+                    false => (false, count),
+                };
+                if count == 0 || j + count > width {
+                    return Err(invalid("bad scanline run length"));
+                }
+                match run {
+                    true => component[j..j + count].fill(byte()?),
+                    false => {
+                        for b in &mut component[j..j + count] {
+                            *b = byte()?;
+                        }
+                    }
+                }
+                j += count;
+            }
+        }
+        for j in 0..width {
+            let rgbe = [0, 1, 2, 3].map(|k| components[k * width + j]);
+            image.data.push(Pixel::from(from_rgbe(rgbe)));
+        }
+    }
+    Ok(image)
+}
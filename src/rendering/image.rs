@@ -1,27 +1,51 @@
 use crate::math::Vec3;
 
+use super::OutputTransform;
+
 use std::fmt;
 use std::fmt::Formatter;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Pixel(Vec3);
 
+// how a stereo pair is packed into a single image, left eye first
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StereoLayout {
+    SideBySide,
+    OverUnder,
+}
+
 pub struct Image {
     pub data: Vec<Pixel>,
     pub rows: usize,
     pub cols: usize,
 }
 
+// an image that may have stopped before every pixel was rendered, the
+// mask is row major like the pixels and marks the rendered ones
+pub struct PartialImage {
+    pub image: Image,
+    pub mask: Vec<bool>,
+}
+
+impl PartialImage {
+    pub fn rendered(&self) -> usize {
+        self.mask.iter().filter(|&&done| done).count()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.mask.iter().all(|&done| done)
+    }
+}
+
 impl Pixel {
-    pub fn default() -> Self {
-        Self(Vec3::default())
+    pub fn color(&self) -> Vec3 {
+        self.0
     }
 
+    // srgb encoded with the default output transform
     pub fn to_rgb(&self) -> (u8, u8, u8) {
-        let r = (255.999 * self.0.x.clamp(0.0, 1.0)) as u8;
-        let g = (255.999 * self.0.y.clamp(0.0, 1.0)) as u8;
-        let b = (255.999 * self.0.z.clamp(0.0, 1.0)) as u8;
-        (r, g, b)
+        OutputTransform::default().encode(self.0)
     }
 }
 
@@ -54,6 +78,42 @@ impl Image {
     pub fn set(&mut self, i: usize, j: usize, p: Pixel) {
         self.data[i * self.cols + j] = p;
     }
+
+    // interleaved 8 bit rgb rows, top to bottom
+    pub fn to_rgb8(&self, transform: &OutputTransform) -> Vec<u8> {
+        self.data
+            .iter()
+            .flat_map(|p| {
+                let (r, g, b) = transform.encode_pixel(p);
+                [r, g, b]
+            })
+            .collect()
+    }
+
+    pub fn stereo(left: &Image, right: &Image, layout: StereoLayout) -> Self {
+        let (rows, cols) = (left.rows, left.cols);
+        let mut result = match layout {
+            StereoLayout::SideBySide => Image::new(2 * cols, rows),
+            StereoLayout::OverUnder => Image::new(cols, 2 * rows),
+        };
+        match layout {
+            StereoLayout::SideBySide => {
+                for i in 0..rows {
+                    result
+                        .data
+                        .extend_from_slice(&left.data[i * cols..][..cols]);
+                    result
+                        .data
+                        .extend_from_slice(&right.data[i * cols..][..cols]);
+                }
+            }
+            StereoLayout::OverUnder => {
+                result.data.extend_from_slice(&left.data);
+                result.data.extend_from_slice(&right.data);
+            }
+        }
+        result
+    }
 }
 
 impl fmt::Display for Image {
@@ -1,13 +1,46 @@
 // modules
+pub mod background;
 pub mod camera;
+pub mod exr;
+pub mod filter;
+pub mod hdr;
 pub mod image;
+pub mod reader;
 pub mod renderer;
+pub mod tonemap;
+pub mod writer;
 
 // flatten
+pub use background::Background;
+
 pub use image::Image;
+pub use image::PartialImage;
 pub use image::Pixel;
+pub use image::StereoLayout;
 
 pub use camera::Camera;
 pub use camera::CameraBuilder;
+pub use camera::CameraError;
+pub use camera::Eye;
+pub use camera::Projection;
+pub use camera::Stereo;
+pub use camera::StereoMode;
+
+pub use filter::Filter;
 
+pub use renderer::RenderError;
 pub use renderer::Renderer;
+
+pub use tonemap::OutputTransform;
+pub use tonemap::ToneMap;
+pub use tonemap::TransformError;
+
+pub use writer::save_image;
+pub use writer::write_image;
+pub use writer::ImageFormat;
+
+pub use reader::load_image;
+pub use reader::read_image;
+
+pub use exr::ExrCompression;
+pub use exr::ExrPixelType;
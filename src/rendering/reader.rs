@@ -0,0 +1,153 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::exr::read_exr;
+use super::hdr::{read_hdr, read_pfm};
+use super::Image;
+use super::ImageFormat;
+
+// reads back the float formats written by save_image
+pub fn load_image(path: &Path) -> io::Result<Image> {
+    let format = ImageFormat::from_path(path);
+    let data = fs::read(path)?;
+    read_image(&data, format).map_err(|e| {
+        io::Error::new(e.kind(), format!("{}: {e}", path.display()))
+    })
+}
+
+pub fn read_image(
+    data: &[u8],
+    format: Option<ImageFormat>,
+) -> io::Result<Image> {
+    match format {
+        Some(ImageFormat::Pfm) => read_pfm(data),
+        Some(ImageFormat::Hdr) => read_hdr(data),
+        Some(ImageFormat::Exr { .. }) => read_exr(data),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "only pfm, hdr and exr images can be read",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec3;
+    use crate::rendering::{
+        write_image, ExrCompression, ExrPixelType, OutputTransform, Pixel,
+    };
+
+    // high dynamic range values, including some far above one
+    fn sample(width: usize, height: usize) -> Image {
+        let mut image = Image::new(width, height);
+        for i in 0..height {
+            for j in 0..width {
+                let (x, y) = (i as f64, j as f64);
+                let color = Vec3::new(0.37 * x, 1.9 * y, 0.01 + x * y);
+                image.data.push(Pixel::from(color));
+            }
+        }
+        image
+    }
+
+    // each channel within tolerance of the brightest channel of its pixel
+    fn round_trip(image: &Image, format: ImageFormat, tolerance: f64) {
+        let mut data = Vec::new();
+        let transform = OutputTransform::default();
+        write_image(&mut data, image, format, &transform).unwrap();
+        let read = read_image(&data, Some(format)).unwrap();
+        assert_eq!((read.cols, read.rows), (image.cols, image.rows));
+        for (a, b) in image.data.iter().zip(&read.data) {
+            let (a, b) = (a.color(), b.color());
+            let scale = a.x.max(a.y).max(a.z).max(1e-3);
+            let error = (a - b).length() / scale;
+            assert!(error <= tolerance, "{format:?}: {a} read as {b}");
+        }
+    }
+
+    #[test]
+    fn pfm_round_trips() {
+        round_trip(&sample(37, 21), ImageFormat::Pfm, 1e-6);
+    }
+
+    #[test]
+    fn hdr_round_trips() {
+        // run length encoded scanlines, and flat ones for narrow images
+        round_trip(&sample(37, 21), ImageFormat::Hdr, 1.0 / 64.0);
+        round_trip(&sample(5, 3), ImageFormat::Hdr, 1.0 / 64.0);
+    }
+
+    #[test]
+    fn exr_round_trips() {
+        let pixels = [(ExrPixelType::Half, 1e-3), (ExrPixelType::Float, 1e-6)];
+        let compressions = [ExrCompression::None, ExrCompression::Zip];
+        for (pixel, tolerance) in pixels {
+            for compression in compressions {
+                let format = ImageFormat::Exr { pixel, compression };
+                round_trip(&sample(37, 21), format, tolerance);
+            }
+        }
+    }
+
+    fn rejects(data: &[u8], format: ImageFormat) {
+        assert!(read_image(data, Some(format)).is_err());
+    }
+
+    #[test]
+    fn huge_dimensions_are_rejected() {
+        let side = usize::MAX / 2;
+        let pfm = format!("PF\n{side} {side}\n-1.0\n");
+        rejects(pfm.as_bytes(), ImageFormat::Pfm);
+        rejects(b"PF\n100000 100000\n-1.0\n\0\0\0\0", ImageFormat::Pfm);
+        let hdr = format!("#?RADIANCE\n\n-Y {side} +X {side}\n");
+        rejects(hdr.as_bytes(), ImageFormat::Hdr);
+        rejects(b"#?RADIANCE\n\n-Y 100000 +X 100000\n", ImageFormat::Hdr);
+    }
+
+    #[test]
+    fn empty_images_are_rejected() {
+        let pixels = [0u8; 48];
+        for header in ["PF\n0 0\n-1.0\n", "PF\n3 0\n-1.0\n", "Pf\n0 3\n1\n"] {
+            let pfm = [header.as_bytes(), &pixels].concat();
+            rejects(&pfm, ImageFormat::Pfm);
+        }
+        for resolution in ["-Y 3 +X 0", "-Y 0 +X 3", "-Y 0 +X 0"] {
+            let hdr = format!("#?RADIANCE\n\n{resolution}\n");
+            let hdr = [hdr.as_bytes(), &pixels].concat();
+            rejects(&hdr, ImageFormat::Hdr);
+        }
+    }
+
+    #[test]
+    fn exr_data_window_is_checked() {
+        let format = ImageFormat::Exr {
+            pixel: ExrPixelType::Half,
+            compression: ExrCompression::Zip,
+        };
+        let mut data = Vec::new();
+        let transform = OutputTransform::default();
+        write_image(&mut data, &sample(4, 4), format, &transform).unwrap();
+        let key = b"dataWindow\0box2i\0";
+        let at = data.windows(key.len()).position(|w| w == key).unwrap();
+        let window = at + key.len() + 4;
+        let patch = |data: &mut Vec<u8>, values: [i32; 4]| {
+            let bytes: Vec<u8> =
+                values.iter().flat_map(|v| v.to_le_bytes()).collect();
+            data[window..window + 16].copy_from_slice(&bytes);
+        };
+        for values in [
+            [i32::MIN, i32::MIN, i32::MAX, i32::MAX],
+            [0, 0, 1 << 20, 1 << 20],
+            [0, 0, 3, 1 << 30],
+            [0, 0, -1, 3],
+            [0, 0, 3, -1],
+            [5, 5, 0, 0],
+        ] {
+            let mut corrupt = data.clone();
+            patch(&mut corrupt, values);
+            rejects(&corrupt, format);
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::materials::Scatter;
 use crate::math::*;
@@ -6,32 +7,51 @@ use crate::objects::*;
 
 use crate::runtime::Job;
 use crate::runtime::Manager;
-use crate::runtime::workpool::BaseQueue;
+use crate::runtime::workpool::StealingQueue;
 use crate::runtime::WorkConfig;
+use crate::runtime::{default_threads, Progress, BATCHES_PER_THREAD};
+use crate::runtime::{CancelToken, JobError};
 
 use super::image::*;
+use super::Background;
 use super::Camera;
+use super::Eye;
+
+// a pixel whose rendering panicked, the input is its (row, column)
+pub type RenderError = JobError<(usize, usize)>;
+
+type ProgressCallback = Box<dyn Fn(&Progress) + Send + Sync>;
 
 pub struct Renderer {
     camera: Arc<Camera>,
-    objects: Arc<ObjectList>,
+    objects: Arc<ObjectBvh>,
+    lights: Arc<LightList>,
+    background: Background,
+    seed: Option<u64>,
+    threads: usize,
+    batches: Option<usize>,
+    // worker threads kept between renders, spawned on first use
+    pool: Mutex<Option<Manager<RendererWorkConfig>>>,
+    progress: Option<ProgressCallback>,
+    cancel: CancelToken,
+    time_budget: Option<Duration>,
 }
 
 struct PixelRenderer {
-    objs: Arc<ObjectList>,
+    objs: Arc<ObjectBvh>,
+    lights: Arc<LightList>,
     cam: Arc<Camera>,
+    background: Background,
+    seed: Option<u64>,
 }
 
 struct RendererWorkConfig;
 
 impl WorkConfig for RendererWorkConfig {
-    const THREAD_COUNT: usize = 8;
-    const BATCH_COUNT: usize = 512;
-
     type Input = (usize, usize);
     type Output = Pixel;
     type Job = PixelRenderer; 
-    type Queue = BaseQueue<(usize, usize)>; 
+    type Queue = StealingQueue<(usize, usize)>; 
 }
 
 impl Renderer {
@@ -41,13 +61,129 @@ impl Renderer {
         ) -> Self {
         Self {
             camera: Arc::new(camera),
-            objects: Arc::new(objects),
+            lights: Arc::new(LightList::new(&objects)),
+            objects: Arc::new(ObjectBvh::new(objects)),
+            background: Background::default(),
+            seed: None,
+            threads: default_threads(),
+            batches: None,
+            pool: Mutex::new(None),
+            progress: None,
+            cancel: CancelToken::new(),
+            time_budget: None,
         }
     }
 
-    pub fn render(&self) -> Image {
-        let (w, h) = (self.camera.image_width, self.camera.image_height);
-        let renderer = PixelRenderer::new(&self.objects, &self.camera);
+    pub fn background(&mut self, background: Background) -> &mut Self {
+        self.background = background;
+        self
+    }
+
+    // makes renders reproducible, each pixel draws its own sequence so the
+    // result does not depend on how work is spread across threads
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // worker threads, defaults to the available parallelism, zero is
+    // taken as one
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // pieces the image is split into, defaults to a fixed number per
+    // thread, zero is taken as one
+    pub fn batches(&mut self, batches: usize) -> &mut Self {
+        self.batches = Some(batches.max(1));
+        self
+    }
+
+    // called on the rendering thread as batches of pixels finish, counts
+    // are in pixels and start over for each image of a stereo pair
+    pub fn on_progress<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(&Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    // cancelling the token stops the renders in progress after the batches
+    // in flight. each render only sees cancels made after it starts, so a
+    // cancel while no render is running has no effect
+    pub fn cancel_token(&mut self, token: CancelToken) -> &mut Self {
+        self.cancel = token;
+        self
+    }
+
+    // wall clock limit for each call to render, shared by both eyes of a
+    // stereo render
+    pub fn time_budget(&mut self, budget: Duration) -> &mut Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    pub fn bvh_stats(&self) -> BvhStats {
+        self.objects.stats()
+    }
+
+    // pixels left out by a cancel or the time budget stay black
+    pub fn render(&self) -> Result<Image, RenderError> {
+        Ok(self.render_partial()?.image)
+    }
+
+    // the image along with which pixels were rendered before a stop
+    pub fn render_partial(&self) -> Result<PartialImage, RenderError> {
+        let cancel = self.cancel.since_now();
+        self.render_camera(&self.camera, self.deadline(), &cancel)
+    }
+
+    // one image per eye, identical when the camera has no stereo rig
+    pub fn render_stereo(&self) -> Result<(Image, Image), RenderError> {
+        let (left, right) = self.render_stereo_partial()?;
+        Ok((left.image, right.image))
+    }
+
+    pub fn render_stereo_partial(
+        &self,
+    ) -> Result<(PartialImage, PartialImage), RenderError> {
+        let (deadline, cancel) = (self.deadline(), self.cancel.since_now());
+        let left = Arc::new(self.camera.eye(Eye::Left));
+        let right = Arc::new(self.camera.eye(Eye::Right));
+        Ok((
+            self.render_camera(&left, deadline, &cancel)?,
+            self.render_camera(&right, deadline, &cancel)?,
+        ))
+    }
+
+    pub fn render_stereo_composite(
+        &self,
+        layout: StereoLayout,
+    ) -> Result<Image, RenderError> {
+        let (left, right) = self.render_stereo()?;
+        Ok(Image::stereo(&left, &right, layout))
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.time_budget.map(|budget| Instant::now() + budget)
+    }
+
+    fn render_camera(
+        &self,
+        camera: &Arc<Camera>,
+        deadline: Option<Instant>,
+        cancel: &CancelToken,
+    ) -> Result<PartialImage, RenderError> {
+        let (w, h) = (camera.image_width, camera.image_height);
+        let renderer = PixelRenderer::new(
+            &self.objects,
+            &self.lights,
+            camera,
+            self.background,
+            self.seed,
+        );
         let rendering = Arc::new(renderer);
         let mut indexes = Vec::with_capacity(w * h);
         for i in 0..h {
@@ -56,11 +192,30 @@ impl Renderer {
                 indexes.push(idx);
             }
         }
-        let manager = Manager::<RendererWorkConfig>::new(&rendering, indexes);
-        manager.execute();
-        let mut result = Image::new(w, h);
-        result.data = manager.join();
-        result
+        let batches =
+            self.batches.unwrap_or(self.threads * BATCHES_PER_THREAD);
+        // the lock also keeps concurrent renders off the same workers
+        let mut pool = self.pool.lock().unwrap();
+        let stale = |m: &Manager<_>| {
+            m.thread_count() != self.threads || m.batch_count() != batches
+        };
+        if pool.as_ref().is_none_or(stale) {
+            *pool = Some(Manager::new(self.threads, batches));
+        }
+        let manager = pool.as_mut().unwrap();
+        manager.execute(&rendering, indexes);
+        let on_progress = |progress: &Progress| {
+            if let Some(callback) = &self.progress {
+                callback(progress);
+            }
+        };
+        let pixels =
+            manager.join_until(cancel, deadline, on_progress)?;
+        let mask = pixels.iter().map(Option::is_some).collect();
+        let mut image = Image::new(w, h);
+        image.data =
+            pixels.into_iter().map(Option::unwrap_or_default).collect();
+        Ok(PartialImage { image, mask })
     }
 }
 
@@ -72,52 +227,89 @@ impl Job<(usize, usize), Pixel> for PixelRenderer {
 }
 
 impl PixelRenderer {
-    pub fn new(objects: &Arc<ObjectList>, camera: &Arc<Camera>) -> Self {
+    pub fn new(
+        objects: &Arc<ObjectBvh>,
+        lights: &Arc<LightList>,
+        camera: &Arc<Camera>,
+        background: Background,
+        seed: Option<u64>,
+    ) -> Self {
         Self {
             objs: Arc::clone(objects),
+            lights: Arc::clone(lights),
             cam: Arc::clone(camera),
+            background,
+            seed,
         }
     }
 
-    fn get_ray(&self, i: usize, j: usize) -> Ray {
-        let default_direction = self.cam.pixel_origin - self.cam.position;
-        let (du, dv) = (self.cam.pixel_delta_u, self.cam.pixel_delta_v);
-        let direction_shift = (dv * i as f64) + (du * j as f64);
-        Ray::new(self.cam.position, default_direction + direction_shift)
+    fn check_hit(&self, r: &Ray) -> (bool, HitRecord<'_>) {
+        let mut record = HitRecord::default();
+        let closest = Interval::new(0.001, f64::INFINITY);
+        let hit = self.objs.hit(r, &closest, &mut record);
+        (hit, record)
     }
 
-    fn check_hit(&self, r: &Ray) -> (bool, HitRecord) {
-        let mut record = HitRecord::default();
-        let mut hit = false;
-        let mut tmp = HitRecord::default();
-        let mut closest = Interval::new(0.001, f64::INFINITY);
-        for object in &self.objs.objects {
-            if object.hit(r, &closest, &mut tmp) == true {
-                hit = true;
-                closest.max = tmp.t;
-                record = tmp;
-            }
+    // shadow ray toward a random point on a light, weighted against the
+    // chance of the bsdf sampling the same direction
+    fn sample_light(&self, r: &Ray, rec: &HitRecord, at: Vec3) -> Vec3 {
+        let (Some(mat), Some(point)) =
+            (rec.mat, self.lights.random_point(rec.point))
+        else {
+            return Vec3::default();
+        };
+        let shadow = Ray::new(rec.point, point - rec.point);
+        let bsdf_pdf = mat.scattering_pdf(r, rec, &shadow);
+        if bsdf_pdf <= 0.0 {
+            return Vec3::default();
         }
-        (hit, record)
+        // the direction is unnormalized, so the light itself is at t = 1
+        let (hit, light_rec) = self.check_hit(&shadow);
+        if !hit || light_rec.t < 1.0 - 1e-6 {
+            return Vec3::default();
+        }
+        let light_pdf = self.lights.pdf_hit(&shadow, light_rec.t);
+        if light_pdf <= 0.0 {
+            return Vec3::default();
+        }
+        let emitted = match light_rec.mat {
+            Some(light_mat) => light_mat.emitted(&light_rec),
+            None => return Vec3::default(),
+        };
+        let weight = power_heuristic(light_pdf, bsdf_pdf);
+        Vec3::hadamard(at, emitted) * (weight * bsdf_pdf / light_pdf)
     }
 
-    fn cast_ray(&self, r: &Ray, depth: usize) -> Vec3 {
-        if depth <= 0 {
+    // bsdf_pdf is the density with which the previous bounce sampled r,
+    // or None for camera rays and specular bounces
+    fn cast_ray(&self, r: &Ray, depth: usize, bsdf_pdf: Option<f64>) -> Vec3 {
+        if depth == 0 {
             return Vec3::default();
         }
-        let (hit, rec) = self.check_hit(&r);
+        let (hit, rec) = self.check_hit(r);
         if !hit {
-            return lerp(
-                &r,
-                Vec3::new(0.5, 0.7, 1.0),
-                Vec3::new(1.0, 1.0, 1.0),
-                );
+            return self.background.color(r);
         }
         let (mut at, mut scattered) = (Vec3::default(), Ray::default());
         if let Some(mat) = rec.mat {
-            mat.scatter(&r, &rec, &mut at, &mut scattered);
-            let cast = self.cast_ray(&scattered, depth - 1);
-            Vec3::new(at.x * cast.x, at.y * cast.y, at.z * cast.z)
+            let mut emitted = mat.emitted(&rec);
+            // only the sampled lights could also have been reached by
+            // sample_light, anything else keeps its full weight
+            if let Some(pdf) = bsdf_pdf.filter(|_| !emitted.near_zero()) {
+                let light_pdf = self.lights.pdf_hit(r, rec.t);
+                emitted *= power_heuristic(pdf, light_pdf);
+            }
+            if !mat.scatter(r, &rec, &mut at, &mut scattered) {
+                return emitted;
+            }
+            let pdf = mat.scattering_pdf(r, &rec, &scattered);
+            if pdf <= 0.0 {
+                let cast = self.cast_ray(&scattered, depth - 1, None);
+                return emitted + Vec3::hadamard(at, cast);
+            }
+            let direct = self.sample_light(r, &rec, at);
+            let cast = self.cast_ray(&scattered, depth - 1, Some(pdf));
+            emitted + direct + Vec3::hadamard(at, cast)
         } else {
             Vec3::default()
         }
@@ -125,14 +317,247 @@ impl PixelRenderer {
 
     pub fn render_pixel(&self, i: usize, j: usize) -> Pixel {
         let (samples, depth) = (self.cam.samples, self.cam.max_depth);
-        let scale = 1.0 / self.cam.samples as f64;
-        let mut color = Vec3::default();
+        let filter = self.cam.filter;
+        if let Some(seed) = self.seed {
+            seed_rng(pixel_seed(seed, i, j));
+        }
+        let (mut color, mut total) = (Vec3::default(), 0.0);
         for _ in 0..samples {
-            let ray = self.get_ray(i, j);
-            color += self.cast_ray(&ray, depth);
+            let (dx, dy, weight) = filter.sample();
+            // directions outside a fisheye circle see nothing
+            if let Some(ray) = self.cam.get_ray(i, j, dx, dy) {
+                color += weight * self.cast_ray(&ray, depth, None);
+            }
+            total += weight;
+        }
+        // negative lobes can cancel out with very few samples
+        match total > 0.0 {
+            true => Pixel::from(color / total),
+            false => Pixel::default(),
         }
-        Pixel::from(color * scale)
     }
 }
 
+// splitmix64 finalizer over the seed and pixel coordinates
+fn pixel_seed(seed: u64, i: usize, j: usize) -> u64 {
+    let mut z = seed ^ ((i as u64) << 32 | j as u64);
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Material;
+    use crate::rendering::CameraBuilder;
+
+    fn pixel_renderer(
+        objects: ObjectList,
+        lights: LightList,
+        background: Background,
+    ) -> PixelRenderer {
+        let camera = CameraBuilder::new()
+            .position(0.0, 1.0, 1.0)
+            .target(0.0, 0.0, 0.0)
+            .build()
+            .unwrap();
+        let lights = Arc::new(lights);
+        let objects = Arc::new(ObjectBvh::new(objects));
+        PixelRenderer::new(
+            &objects,
+            &lights,
+            &Arc::new(camera),
+            background,
+            None,
+        )
+    }
+
+    // y = 0 ground plane under a white sky
+    fn ground(mat: Material) -> PixelRenderer {
+        let mut objects = ObjectList::new();
+        let q = Vec3::new(-100.0, 0.0, -100.0);
+        let (u, v) = (Vec3::new(0.0, 0.0, 200.0), Vec3::new(200.0, 0.0, 0.0));
+        objects.add_quad(q, u, v, mat);
+        let sky = Vec3::new(1.0, 1.0, 1.0);
+        let lights = LightList::new(&objects);
+        pixel_renderer(objects, lights, Background::Solid(sky))
+    }
+
+    // a 2x2 horizontal quad centered over the origin at height y
+    fn ceiling(y: f64) -> (Vec3, Vec3, Vec3) {
+        let q = Vec3::new(-1.0, y, -1.0);
+        (q, Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0))
+    }
+
+    fn straight_up() -> Ray {
+        Ray::new(Vec3::default(), Vec3::new(0.0, 1.0, 0.0))
+    }
+
+    // a small seeded render of a sphere on the ground under a white sky
+    fn small_renderer() -> Renderer {
+        let camera = CameraBuilder::new()
+            .resolution(24, 16)
+            .samples(2)
+            .max_depth(4)
+            .position(0.0, 1.0, 3.0)
+            .target(0.0, 0.0, 0.0)
+            .build()
+            .unwrap();
+        let mut objects = ObjectList::new();
+        let mat = Material::new_diffuse(0.5, 0.5, 0.5);
+        objects.add_sphere(1.0, 0.0, 0.0, 0.0, mat.clone());
+        objects.add_sphere(100.0, 0.0, -101.0, 0.0, mat);
+        let mut renderer = Renderer::new(camera, objects);
+        renderer
+            .background(Background::Solid(Vec3::new(1.0, 1.0, 1.0)))
+            .seed(7);
+        renderer
+    }
+
+    // renders partially, checking that exactly the pixels outside the
+    // mask are black and that the last progress report says it is done.
+    // the token is cancelled on the first progress report
+    fn stopped_render(
+        renderer: &mut Renderer,
+        token: CancelToken,
+    ) -> PartialImage {
+        let last = Arc::new(Mutex::new(None));
+        let seen = Arc::clone(&last);
+        renderer
+            .threads(2)
+            .batches(64)
+            .on_progress(move |progress| {
+                token.cancel();
+                *seen.lock().unwrap() = Some(*progress);
+            });
+        let partial = renderer.render_partial().unwrap();
+        let pixels = partial.image.data.iter().zip(&partial.mask);
+        for (pixel, &rendered) in pixels {
+            assert_eq!(*pixel != Pixel::default(), rendered);
+        }
+        let total = partial.mask.len();
+        assert!(partial.rendered() < total, "the render was not stopped");
+        let last = last.lock().unwrap().expect("progress was reported");
+        assert!(last.stopped && last.is_done());
+        assert_eq!(last.items, partial.rendered());
+        partial
+    }
+
+    #[test]
+    fn cancelled_renders_stop_and_the_next_one_runs_in_full() {
+        let token = CancelToken::new();
+        let mut renderer = small_renderer();
+        renderer.cancel_token(token.clone());
+        stopped_render(&mut renderer, token.clone());
+        // a cancel between renders, as one arriving just after a render
+        // returns would be, is not carried over to the next render
+        token.cancel();
+        renderer.on_progress(|_| {});
+        let full = renderer.render_partial().unwrap();
+        assert_eq!(full.rendered(), full.mask.len());
+        let expected = small_renderer().render().unwrap();
+        assert_eq!(full.image.data, expected.data);
+    }
+
+    #[test]
+    fn zero_budget_renders_keep_what_finished() {
+        let mut renderer = small_renderer();
+        renderer.time_budget(Duration::ZERO);
+        stopped_render(&mut renderer, CancelToken::new());
+    }
 
+    #[test]
+    fn zero_threads_and_batches_are_taken_as_one() {
+        let expected = small_renderer().threads(2).render().unwrap();
+        let mut renderer = small_renderer();
+        renderer.threads(0).batches(0);
+        assert_eq!(renderer.render().unwrap().data, expected.data);
+        let pool = renderer.pool.lock().unwrap();
+        let manager = pool.as_ref().unwrap();
+        assert_eq!((manager.thread_count(), manager.batch_count()), (1, 1));
+    }
+
+    #[test]
+    fn absorbed_rays_carry_no_light() {
+        seed_rng(1);
+        // a fuzzy mirror hit at a grazing angle sends many reflections
+        // below the surface, those end the path instead of reaching the sky
+        let renderer = ground(Material::new_metal(1.0, 1.0, 1.0, 1.0));
+        let origin = Vec3::new(0.0, 0.1, -10.0);
+        let r = Ray::new(origin, Vec3::new(0.0, -0.01, 1.0));
+        let n = 1000;
+        let total: f64 = (0..n).map(|_| renderer.cast_ray(&r, 2, None).x).sum();
+        let mean = total / n as f64;
+        assert!(mean > 0.1 && mean < 0.9, "mean radiance {mean}");
+    }
+
+    #[test]
+    fn emitters_that_are_not_sampled_keep_full_weight() {
+        // cubes are never sampled directly, even with a light behind them
+        let mut objects = ObjectList::new();
+        let glow = Material::new_emissive(1.0, 1.0, 1.0, 2.0);
+        objects.add_cube(2.0, -1.0, 2.0, -1.0, glow);
+        let (q, u, v) = ceiling(6.0);
+        objects.add_quad(q, u, v, Material::new_emissive(1.0, 1.0, 1.0, 5.0));
+        let lights = LightList::new(&objects);
+        assert_eq!(lights.len(), 1);
+        let renderer = pixel_renderer(objects, lights, Background::None);
+        let color = renderer.cast_ray(&straight_up(), 1, Some(1.0));
+        assert_eq!(color, Vec3::new(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn lights_behind_the_hit_light_do_not_count() {
+        let mut objects = ObjectList::new();
+        let (q, u, v) = ceiling(2.0);
+        objects.add_quad(q, u, v, Material::new_emissive(1.0, 1.0, 1.0, 2.0));
+        let (q, u, v) = ceiling(4.0);
+        objects.add_quad(q, u, v, Material::new_emissive(1.0, 1.0, 1.0, 5.0));
+        let lights = LightList::new(&objects);
+        assert_eq!(lights.len(), 2);
+        let renderer = pixel_renderer(objects, lights, Background::None);
+        let color = renderer.cast_ray(&straight_up(), 1, Some(1.0));
+        // the front light is 2 away with area 4, a density of 1 halved
+        // for being one of two lights
+        let weight = power_heuristic(1.0, 0.5);
+        assert!((color.x - 2.0 * weight).abs() < 1e-9, "{color:?}");
+    }
+
+    #[test]
+    fn light_sampling_reduces_variance() {
+        // a small bright light over a diffuse floor, seen from the side
+        let mut objects = ObjectList::new();
+        let q = Vec3::new(-10.0, 0.0, -10.0);
+        let (u, v) = (Vec3::new(0.0, 0.0, 20.0), Vec3::new(20.0, 0.0, 0.0));
+        objects.add_quad(q, u, v, Material::new_diffuse(0.5, 0.5, 0.5));
+        let q = Vec3::new(-0.1, 1.0, -0.1);
+        let (u, v) = (Vec3::new(0.2, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.2));
+        let light = Material::new_emissive(1.0, 1.0, 1.0, 50.0);
+        objects.add_quad(q, u, v, light);
+        let sampled = LightList::new(&objects);
+        let unsampled = LightList::new(&ObjectList::new());
+        let origin = Vec3::new(0.0, 0.5, -2.0);
+        let r = Ray::new(origin, Vec3::default() - origin);
+        let n = 20000;
+        let stats = |lights: LightList| {
+            seed_rng(7);
+            let renderer =
+                pixel_renderer(objects.clone(), lights, Background::None);
+            let samples: Vec<f64> =
+                (0..n).map(|_| renderer.cast_ray(&r, 2, None).x).collect();
+            let mean = samples.iter().sum::<f64>() / n as f64;
+            let squares: f64 = samples.iter().map(|x| (x - mean).powi(2)).sum();
+            (mean, squares / (n - 1) as f64)
+        };
+        let (mis_mean, mis_variance) = stats(sampled);
+        let (bsdf_mean, bsdf_variance) = stats(unsampled);
+        let bias = (mis_mean - bsdf_mean).abs() / bsdf_mean;
+        assert!(bias < 0.15, "means {mis_mean} and {bsdf_mean}");
+        assert!(
+            bsdf_variance > 10.0 * mis_variance,
+            "variances {mis_variance} and {bsdf_variance}"
+        );
+    }
+}
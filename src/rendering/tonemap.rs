@@ -0,0 +1,260 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::math::Vec3;
+
+use super::Pixel;
+
+// maps scene referred radiance into the displayable [0, 1] range
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum ToneMap {
+    // hard clip, bright areas blow out
+    #[default]
+    Clamp,
+    Reinhard,
+    // reinhard that reaches white at the given radiance instead of never
+    ExtendedReinhard {
+        white: f64,
+    },
+    // Stephen Hill's fit of the ACES reference and output transforms
+    Aces,
+    // Benjamin Wrensch's polynomial approximation of the AgX base look
+    Agx,
+}
+
+// conversion from rendered radiance to 8 bit display values, exposure is
+// in stops and applied before the tone map
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OutputTransform {
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+    pub srgb: bool,
+}
+
+impl Default for OutputTransform {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_map: ToneMap::default(),
+            srgb: true,
+        }
+    }
+}
+
+// a transform setting outside the range where it means anything
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformError {
+    pub field: &'static str,
+    pub value: f64,
+    pub expected: &'static str,
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            field,
+            value,
+            expected,
+        } = self;
+        write!(f, "{field} must be {expected}, got {value}")
+    }
+}
+
+impl Error for TransformError {}
+
+impl OutputTransform {
+    pub fn new(
+        exposure: f64,
+        tone_map: ToneMap,
+    ) -> Result<Self, TransformError> {
+        let invalid = |field, value, expected| TransformError {
+            field,
+            value,
+            expected,
+        };
+        if !exposure.is_finite() {
+            return Err(invalid("exposure", exposure, "finite"));
+        }
+        if let ToneMap::ExtendedReinhard { white } = tone_map {
+            if white.is_nan() || white <= 0.0 {
+                return Err(invalid("white", white, "positive"));
+            }
+        }
+        Ok(Self {
+            exposure,
+            tone_map,
+            srgb: true,
+        })
+    }
+
+    // display referred linear color in [0, 1], before encoding
+    pub fn apply(&self, color: Vec3) -> Vec3 {
+        let c = 2f64.powf(self.exposure) * color;
+        let c = Vec3::new(c.x.max(0.0), c.y.max(0.0), c.z.max(0.0));
+        let mapped = match self.tone_map {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => per_channel(c, |x| x / (1.0 + x)),
+            ToneMap::ExtendedReinhard { white } => {
+                let w2 = white * white;
+                per_channel(c, |x| x * (1.0 + x / w2) / (1.0 + x))
+            }
+            ToneMap::Aces => aces(c),
+            ToneMap::Agx => agx(c),
+        };
+        per_channel(mapped, |x| x.clamp(0.0, 1.0))
+    }
+
+    // display color in [0, 1] after the transfer function
+    pub fn display(&self, color: Vec3) -> Vec3 {
+        let c = self.apply(color);
+        match self.srgb {
+            true => per_channel(c, srgb_encode),
+            false => c,
+        }
+    }
+
+    pub fn encode(&self, color: Vec3) -> (u8, u8, u8) {
+        let c = self.display(color);
+        let quantize = |x: f64| (255.999 * x) as u8;
+        (quantize(c.x), quantize(c.y), quantize(c.z))
+    }
+
+    pub fn encode16(&self, color: Vec3) -> (u16, u16, u16) {
+        let c = self.display(color);
+        let quantize = |x: f64| (65535.999 * x) as u16;
+        (quantize(c.x), quantize(c.y), quantize(c.z))
+    }
+
+    pub fn encode_pixel(&self, pixel: &Pixel) -> (u8, u8, u8) {
+        self.encode(pixel.color())
+    }
+}
+
+pub fn srgb_encode(x: f64) -> f64 {
+    match x <= 0.0031308 {
+        true => 12.92 * x,
+        false => 1.055 * x.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+fn per_channel<F: Fn(f64) -> f64>(c: Vec3, f: F) -> Vec3 {
+    Vec3::new(f(c.x), f(c.y), f(c.z))
+}
+
+// rows of a 3x3 matrix applied to a column vector
+fn transform(m: [[f64; 3]; 3], c: Vec3) -> Vec3 {
+    let row = |r: [f64; 3]| r[0] * c.x + r[1] * c.y + r[2] * c.z;
+    Vec3::new(row(m[0]), row(m[1]), row(m[2]))
+}
+
+fn aces(c: Vec3) -> Vec3 {
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let fit = |v: f64| {
+        (v * (v + 0.0245786) - 0.000090537)
+            / (v * (0.983729 * v + 0.4329510) + 0.238081)
+    };
+    transform(OUTPUT, per_channel(transform(INPUT, c), fit))
+}
+
+fn agx(c: Vec3) -> Vec3 {
+    const INSET: [[f64; 3]; 3] = [
+        [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+        [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+        [0.0423756549057051, 0.0784336, 0.879142973793104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+        [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+        [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+    ];
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+    let contrast = |v: f64| {
+        let x = (v.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV)
+            / (MAX_EV - MIN_EV);
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x
+            + 0.4298 * x2
+            + 0.1191 * x
+            - 0.00232
+    };
+    let look = transform(OUTSET, per_channel(transform(INSET, c), contrast));
+    // the curve produces display encoded values, undo the 2.2 gamma so
+    // the shared srgb encode can be applied afterwards
+    per_channel(look, |x| x.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the mapped value of a grey input, checking the channels agree
+    fn grey(tone_map: ToneMap, x: f64) -> f64 {
+        let transform = OutputTransform::new(0.0, tone_map).unwrap();
+        let c = transform.apply(Vec3::new(x, x, x));
+        assert!((c.x - c.y).abs() < 1e-3 && (c.y - c.z).abs() < 1e-3);
+        c.y
+    }
+
+    fn close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} vs {expected}");
+    }
+
+    #[test]
+    fn reinhard_curves() {
+        close(grey(ToneMap::Reinhard, 0.0), 0.0);
+        close(grey(ToneMap::Reinhard, 1.0), 0.5);
+        close(grey(ToneMap::Reinhard, 999.0), 0.999);
+        let extended = ToneMap::ExtendedReinhard { white: 4.0 };
+        close(grey(extended, 0.0), 0.0);
+        close(grey(extended, 1.0), 0.53125);
+        close(grey(extended, 4.0), 1.0);
+        close(grey(extended, 1e6), 1.0);
+    }
+
+    #[test]
+    fn aces_curve() {
+        close(grey(ToneMap::Aces, 0.0), 0.0);
+        close(grey(ToneMap::Aces, 0.18), 0.1055912);
+        close(grey(ToneMap::Aces, 1.0), 0.6191154);
+        close(grey(ToneMap::Aces, 1e4), 1.0);
+    }
+
+    #[test]
+    fn agx_curve() {
+        close(grey(ToneMap::Agx, 0.0), 0.0);
+        close(grey(ToneMap::Agx, 0.18), 0.2145327);
+        close(grey(ToneMap::Agx, 1.0), 0.5902072);
+        close(grey(ToneMap::Agx, 1e4), 0.9969776);
+    }
+
+    #[test]
+    fn srgb_encoding() {
+        close(srgb_encode(0.0), 0.0);
+        close(srgb_encode(0.0031308), 0.0404499);
+        close(srgb_encode(0.18), 0.4613561);
+        close(srgb_encode(1.0), 1.0);
+        let transform = OutputTransform::default();
+        assert_eq!(transform.encode(Vec3::new(0.18, 0.0, 2.0)), (118, 0, 255));
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        for white in [0.0, -1.0, f64::NAN] {
+            let tone_map = ToneMap::ExtendedReinhard { white };
+            let err = OutputTransform::new(0.0, tone_map).unwrap_err();
+            assert_eq!(err.field, "white");
+        }
+        let err = OutputTransform::new(f64::NAN, ToneMap::Clamp).unwrap_err();
+        assert_eq!(err.to_string(), "exposure must be finite, got NaN");
+    }
+}
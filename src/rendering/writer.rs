@@ -0,0 +1,237 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::exr::{write_exr, ExrCompression, ExrPixelType};
+use super::hdr::{write_hdr, write_pfm};
+use super::Image;
+use super::OutputTransform;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ImageFormat {
+    // binary P6
+    Ppm,
+    Png {
+        sixteen_bit: bool,
+    },
+    // float formats store the raw radiance and skip the output transform
+    Pfm,
+    Hdr,
+    Exr {
+        pixel: ExrPixelType,
+        compression: ExrCompression,
+    },
+}
+
+impl ImageFormat {
+    // by extension, png defaults to 8 bits per channel and exr to zip
+    // compressed half floats
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "ppm" => Some(Self::Ppm),
+            "png" => Some(Self::Png { sixteen_bit: false }),
+            "pfm" => Some(Self::Pfm),
+            "hdr" => Some(Self::Hdr),
+            "exr" => Some(Self::Exr {
+                pixel: ExrPixelType::default(),
+                compression: ExrCompression::default(),
+            }),
+            _ => None,
+        }
+    }
+
+    // short names for picking a format explicitly, png16 and exr-float
+    // select the wider pixel types
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png16" => Some(Self::Png { sixteen_bit: true }),
+            "exr-float" => Some(Self::Exr {
+                pixel: ExrPixelType::Float,
+                compression: ExrCompression::default(),
+            }),
+            ext => Self::from_path(Path::new(&format!("image.{ext}"))),
+        }
+    }
+}
+
+// writes to a file whose extension picks the format
+pub fn save_image(
+    path: &Path,
+    image: &Image,
+    transform: &OutputTransform,
+) -> io::Result<()> {
+    let Some(format) = ImageFormat::from_path(path) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported image format: {}", path.display()),
+        ));
+    };
+    let out = BufWriter::new(File::create(path)?);
+    write_image(out, image, format, transform)
+}
+
+// rows are encoded and written one at a time
+pub fn write_image<W: Write>(
+    out: W,
+    image: &Image,
+    format: ImageFormat,
+    transform: &OutputTransform,
+) -> io::Result<()> {
+    match format {
+        ImageFormat::Ppm => write_ppm(out, image, transform),
+        ImageFormat::Png { sixteen_bit } => {
+            write_png(out, image, transform, sixteen_bit)
+                .map_err(io::Error::other)
+        }
+        ImageFormat::Pfm => write_pfm(out, image),
+        ImageFormat::Hdr => write_hdr(out, image),
+        ImageFormat::Exr { pixel, compression } => {
+            write_exr(out, image, pixel, compression)
+        }
+    }
+}
+
+fn write_ppm<W: Write>(
+    mut out: W,
+    image: &Image,
+    transform: &OutputTransform,
+) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", image.cols, image.rows)?;
+    let mut row = Vec::with_capacity(3 * image.cols);
+    for i in 0..image.rows {
+        row.clear();
+        for j in 0..image.cols {
+            let (r, g, b) = transform.encode_pixel(image.get(i, j));
+            row.extend_from_slice(&[r, g, b]);
+        }
+        out.write_all(&row)?;
+    }
+    out.flush()
+}
+
+fn write_png<W: Write>(
+    out: W,
+    image: &Image,
+    transform: &OutputTransform,
+    sixteen_bit: bool,
+) -> Result<(), png::EncodingError> {
+    let mut encoder =
+        png::Encoder::new(out, image.cols as u32, image.rows as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(match sixteen_bit {
+        true => png::BitDepth::Sixteen,
+        false => png::BitDepth::Eight,
+    });
+    if transform.srgb {
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    }
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+    let mut row = Vec::with_capacity(6 * image.cols);
+    for i in 0..image.rows {
+        row.clear();
+        for j in 0..image.cols {
+            let color = image.get(i, j).color();
+            match sixteen_bit {
+                true => {
+                    let (r, g, b) = transform.encode16(color);
+                    for c in [r, g, b] {
+                        row.extend_from_slice(&c.to_be_bytes());
+                    }
+                }
+                false => {
+                    let (r, g, b) = transform.encode(color);
+                    row.extend_from_slice(&[r, g, b]);
+                }
+            }
+        }
+        stream.write_all(&row)?;
+    }
+    stream.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::math::Vec3;
+    use crate::rendering::Pixel;
+
+    // crc-32 as png uses it, bit by bit
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in bytes {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = match crc & 1 {
+                    1 => (crc >> 1) ^ 0xedb8_8320,
+                    _ => crc >> 1,
+                };
+            }
+        }
+        !crc
+    }
+
+    fn be32(bytes: &[u8]) -> u32 {
+        u32::from_be_bytes(bytes[..4].try_into().unwrap())
+    }
+
+    // the chunks after the signature as (type, data), checking each crc
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = be32(rest) as usize;
+            let (body, tail) = rest[4..].split_at(4 + len);
+            let kind = body[..4].try_into().unwrap();
+            assert_eq!(be32(tail), crc32(body), "crc of {kind:?}");
+            chunks.push((kind, &body[4..]));
+            rest = &tail[4..];
+        }
+        chunks
+    }
+
+    fn encode(sixteen_bit: bool) -> Vec<u8> {
+        let (width, height) = (5, 3);
+        let mut image = Image::new(width, height);
+        for k in 0..width * height {
+            let x = k as f64 / 15.0;
+            image.data.push(Pixel::from(Vec3::new(x, 1.0 - x, 0.5)));
+        }
+        let mut out = Vec::new();
+        let format = ImageFormat::Png { sixteen_bit };
+        write_image(&mut out, &image, format, &OutputTransform::default())
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn png_files_are_well_formed() {
+        for (sixteen_bit, depth) in [(false, 8), (true, 16)] {
+            let png = encode(sixteen_bit);
+            assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+            let chunks = chunks(&png);
+            let (kind, header) = chunks[0];
+            assert_eq!(&kind, b"IHDR");
+            assert_eq!((be32(header), be32(&header[4..])), (5, 3));
+            // bit depth, then color type 2 for rgb
+            assert_eq!(header[8..10], [depth, 2]);
+            assert!(chunks.iter().any(|(kind, _)| kind == b"sRGB"));
+            assert_eq!(chunks.last().unwrap(), &(*b"IEND", &[][..]));
+            // each row is a filter byte and three channels per pixel
+            let idat: Vec<u8> = chunks
+                .iter()
+                .filter(|(kind, _)| kind == b"IDAT")
+                .flat_map(|(_, data)| data.iter().copied())
+                .collect();
+            let mut rows = Vec::new();
+            flate2::read::ZlibDecoder::new(&idat[..])
+                .read_to_end(&mut rows)
+                .unwrap();
+            assert_eq!(rows.len(), 3 * (1 + 5 * 3 * depth as usize / 8));
+        }
+    }
+}
@@ -62,18 +62,63 @@ impl<T> Batcher<T> {
         Self { items }
     }
 
+    // splits the items into batch_count batches whose sizes differ by at
+    // most one, the first len % batch_count of them take the extra items.
+    // there are fewer batches when there are fewer items than that
     pub fn create_batches(
         &mut self,
         batch_count: usize
     ) -> VecDeque<WorkBatch<T>> {
-        assert!(self.items.len() % batch_count == 0);
+        let batch_count = batch_count.clamp(1, self.items.len().max(1));
         let batch_size = self.items.len() / batch_count;
+        let remainder = self.items.len() % batch_count;
         let mut work_batches = VecDeque::with_capacity(batch_count);
-        for i in 0..batch_count {
-            let batch = self.items.drain(..batch_size).collect();
-            let batch = WorkBatch::new(i, batch);
-            work_batches.push_back(batch);
+        while !self.items.is_empty() {
+            let id = work_batches.len();
+            let size = batch_size + usize::from(id < remainder);
+            let batch = self.items.drain(..size).collect();
+            work_batches.push_back(WorkBatch::new(id, batch));
         }
         work_batches
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sizes(len: usize, batch_count: usize) -> Vec<usize> {
+        let items: Vec<usize> = (0..len).collect();
+        let batches = Batcher::new(items).create_batches(batch_count);
+        let mut next = 0;
+        for (id, batch) in batches.iter().enumerate() {
+            assert_eq!(batch.id, id);
+            for &item in &batch.items {
+                assert_eq!(item, next, "items stay in order");
+                next += 1;
+            }
+        }
+        assert_eq!(next, len, "every item is batched");
+        batches.iter().map(|batch| batch.items.len()).collect()
+    }
+
+    #[test]
+    fn uneven_items_make_exactly_batch_count_batches() {
+        let cases = [(1000 * 333, 512), (150, 100), (1000, 7), (97, 13)];
+        for (len, batch_count) in cases {
+            let sizes = sizes(len, batch_count);
+            assert_eq!(sizes.len(), batch_count, "{len} items");
+            let (min, max) = (sizes[batch_count - 1], sizes[0]);
+            assert!(max - min <= 1, "{len} items: {min} to {max}");
+            assert!(sizes.windows(2).all(|w| w[0] >= w[1]));
+        }
+        assert_eq!(sizes(150, 100)[49..51], [2, 1]);
+    }
+
+    #[test]
+    fn few_items_make_one_batch_each() {
+        assert_eq!(sizes(3, 8), [1, 1, 1]);
+        assert_eq!(sizes(5, 0), [5]);
+        assert!(sizes(0, 8).is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+// shared handle for stopping a run from another thread. clones refer to the
+// same count of cancels, a token is cancelled once the count has moved past
+// the point it was taken at
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancels: Arc<AtomicU64>,
+    seen: u64,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancels.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancels.load(Ordering::Acquire) != self.seen
+    }
+
+    // a token sharing the same count that only sees cancels made from now
+    // on, so each run can ignore the cancels meant for earlier ones
+    pub fn since_now(&self) -> Self {
+        Self {
+            cancels: Arc::clone(&self.cancels),
+            seen: self.cancels.load(Ordering::Acquire),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_only_see_cancels_made_after_them() {
+        let token = CancelToken::new();
+        let first = token.since_now();
+        token.cancel();
+        let second = token.since_now();
+        assert!(token.is_cancelled() && first.is_cancelled());
+        assert!(!second.is_cancelled());
+        first.cancel();
+        assert!(second.is_cancelled());
+    }
+}
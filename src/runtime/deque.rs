@@ -1,62 +1,371 @@
-use std::sync::atomic::AtomicUsize;
+use std::cell::Cell;
+use std::marker::PhantomData;
 
-// lint macro used to suppress warnings while working on other changes
+// loom swaps in its own atomics to model check the deque, see the
+// loom_tests module
+#[cfg(loom)]
+use loom::sync::atomic::{fence, AtomicIsize, AtomicPtr, Ordering};
+#[cfg(loom)]
+use loom::sync::{Arc, Mutex};
+#[cfg(not(loom))]
+use std::sync::atomic::{fence, AtomicIsize, AtomicPtr, Ordering};
+#[cfg(not(loom))]
+use std::sync::{Arc, Mutex};
 
-#[allow(dead_code)]
-struct RingBuffer<T, const C: usize> {
-    buff: [T; C],
-    capacity: usize,
+// Chase-Lev work stealing deque, following "Correct and Efficient
+// Work-Stealing for Weak Memory Models" by Lê, Pop, Cohen and Zappa Nardelli.
+// the owner pushes and pops at the bottom while thieves take from the top.
+// slots hold boxed items so reading one never races with a write to it
+
+const MIN_CAPACITY: usize = 32;
+
+struct RingBuffer<T> {
+    slots: Box<[AtomicPtr<T>]>,
     mask: usize,
 }
 
-#[allow(dead_code)]
-impl<T, const C: usize> RingBuffer<T, C>
-where
-    T: Sized + Default + Clone + Copy,
-{
-    // capacity must be some n where n = 2^k 
-    pub fn new() -> Self {
+impl<T> RingBuffer<T> {
+    // capacity must be some n where n = 2^k
+    fn new(capacity: usize) -> Self {
+        debug_assert!(capacity.is_power_of_two());
+        let slots = (0..capacity).map(|_| AtomicPtr::default()).collect();
+        Self {
+            slots,
+            mask: capacity - 1,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn store(&self, i: isize, val: *mut T) {
+        self.slots[i as usize & self.mask].store(val, Ordering::Relaxed);
+    }
+
+    fn load(&self, i: isize) -> *mut T {
+        self.slots[i as usize & self.mask].load(Ordering::Relaxed)
+    }
+}
+
+struct Deque<T> {
+    top: AtomicIsize,
+    bottom: AtomicIsize,
+    buffer: AtomicPtr<RingBuffer<T>>,
+    // buffers replaced by a grow, thieves may still be reading them so they
+    // live as long as the deque, boxed so they stay where the thieves saw them
+    #[allow(clippy::vec_box)]
+    retired: Mutex<Vec<Box<RingBuffer<T>>>>,
+    // the raw pointers own their items, and keep the automatic Send and
+    // Sync off so the handles can require T: Send themselves
+    _owns: PhantomData<*mut T>,
+}
+
+impl<T> Deque<T> {
+    fn new() -> Self {
+        let buffer = Box::new(RingBuffer::new(MIN_CAPACITY));
         Self {
-            buff: [T::default(); C],
-            capacity: C,
-            mask: C - 1,
+            top: AtomicIsize::new(0),
+            bottom: AtomicIsize::new(0),
+            buffer: AtomicPtr::new(Box::into_raw(buffer)),
+            retired: Mutex::new(Vec::new()),
+            _owns: PhantomData,
+        }
+    }
+
+    fn len(&self) -> usize {
+        let b = self.bottom.load(Ordering::Relaxed);
+        let t = self.top.load(Ordering::Relaxed);
+        (b - t).max(0) as usize
+    }
+
+    // doubles the buffer, only called by the owner
+    fn grow(&self, t: isize, b: isize) -> &RingBuffer<T> {
+        let old = self.buffer.load(Ordering::Relaxed);
+        // SAFETY: the current buffer is only freed when the deque drops
+        let old_ref = unsafe { &*old };
+        let new = RingBuffer::new(2 * old_ref.capacity());
+        for i in t..b {
+            new.store(i, old_ref.load(i));
         }
+        let new = Box::into_raw(Box::new(new));
+        self.buffer.store(new, Ordering::Release);
+        // SAFETY: old came from Box::into_raw and is no longer current
+        let old = unsafe { Box::from_raw(old) };
+        self.retired.lock().unwrap().push(old);
+        // SAFETY: just stored, see above
+        unsafe { &*new }
     }
 
-    pub fn capacity(&self) -> usize {
-        self.capacity
+    fn push(&self, item: T) {
+        let b = self.bottom.load(Ordering::Relaxed);
+        let t = self.top.load(Ordering::Acquire);
+        // SAFETY: the current buffer is only freed when the deque drops
+        let mut buffer = unsafe { &*self.buffer.load(Ordering::Relaxed) };
+        if b - t > buffer.capacity() as isize - 1 {
+            buffer = self.grow(t, b);
+        }
+        buffer.store(b, Box::into_raw(Box::new(item)));
+        fence(Ordering::Release);
+        self.bottom.store(b + 1, Ordering::Relaxed);
     }
 
-    pub fn store(&mut self, i: usize, val: T) {
-        self.buff[i & self.mask] = val;
+    fn pop(&self) -> Option<T> {
+        let b = self.bottom.load(Ordering::Relaxed) - 1;
+        // SAFETY: the current buffer is only freed when the deque drops
+        let buffer = unsafe { &*self.buffer.load(Ordering::Relaxed) };
+        self.bottom.store(b, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        let t = self.top.load(Ordering::Relaxed);
+        if t > b {
+            self.bottom.store(b + 1, Ordering::Relaxed);
+            return None;
+        }
+        let item = buffer.load(b);
+        if t == b {
+            // the last item, race the thieves for it
+            let won = self
+                .top
+                .compare_exchange(t, t + 1, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok();
+            self.bottom.store(b + 1, Ordering::Relaxed);
+            if !won {
+                return None;
+            }
+        }
+        // SAFETY: the slot was claimed, nobody else will take the item
+        Some(*unsafe { Box::from_raw(item) })
+    }
+
+    // None when empty, retries while losing races with other thieves
+    fn steal(&self) -> Option<T> {
+        loop {
+            let t = self.top.load(Ordering::Acquire);
+            fence(Ordering::SeqCst);
+            let b = self.bottom.load(Ordering::Acquire);
+            if t >= b {
+                return None;
+            }
+            let buffer = self.buffer.load(Ordering::Acquire);
+            // SAFETY: buffers are freed only when the deque drops, and the
+            // slot at t is not overwritten while top is still t
+            let item = unsafe { &*buffer }.load(t);
+            let claimed = self
+                .top
+                .compare_exchange(t, t + 1, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok();
+            if claimed {
+                // SAFETY: the slot was claimed, nobody else will take it
+                return Some(*unsafe { Box::from_raw(item) });
+            }
+        }
     }
+}
 
-    pub fn load(&self, i: usize) -> T {
-        self.buff[i & self.mask]
+impl<T> Drop for Deque<T> {
+    fn drop(&mut self) {
+        let t = self.top.load(Ordering::Relaxed);
+        let b = self.bottom.load(Ordering::Relaxed);
+        // SAFETY: no handles remain, so the buffer and the items left in
+        // it are owned here
+        let buffer =
+            unsafe { Box::from_raw(self.buffer.load(Ordering::Relaxed)) };
+        for i in t..b {
+            drop(unsafe { Box::from_raw(buffer.load(i)) });
+        }
     }
 }
 
-#[allow(dead_code)]
-pub struct Deque<T, const C: usize> {
-    top: AtomicUsize,
-    bottom: AtomicUsize, 
-    buffer: RingBuffer<T, C>,
+// the single owner of a deque, it is Send but not Sync so push and pop
+// always happen on one thread at a time
+pub struct Owner<T> {
+    deque: Arc<Deque<T>>,
+    _not_sync: PhantomData<Cell<()>>,
 }
 
-impl<T, const C: usize> Deque<T, C>
-where
-    T: Sized + Default + Clone + Copy,
-{
+// a handle for taking items from the top of another thread's deque
+pub struct Stealer<T> {
+    deque: Arc<Deque<T>>,
+}
+
+// SAFETY: items move between threads through the deque, the handles
+// themselves only share atomics
+unsafe impl<T: Send> Send for Owner<T> {}
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+impl<T> Default for Owner<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Owner<T> {
     pub fn new() -> Self {
         Self {
-            top: AtomicUsize::new(0),
-            bottom: AtomicUsize::new(0),
-            buffer: RingBuffer::<T, C>::new(),
+            deque: Arc::new(Deque::new()),
+            _not_sync: PhantomData,
         }
     }
+
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            deque: Arc::clone(&self.deque),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.deque.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn push(&self, item: T) {
+        self.deque.push(item);
+    }
+
+    // most recently pushed first
+    pub fn pop(&self) -> Option<T> {
+        self.deque.pop()
+    }
 }
 
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            deque: Arc::clone(&self.deque),
+        }
+    }
+}
 
+impl<T> Stealer<T> {
+    pub fn is_empty(&self) -> bool {
+        self.deque.len() == 0
+    }
+
+    // oldest first
+    pub fn steal(&self) -> Option<T> {
+        self.deque.steal()
+    }
+}
 
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
 
+    use super::*;
 
+    #[test]
+    fn owner_pops_newest_and_thieves_steal_oldest() {
+        let owner = Owner::new();
+        let stealer = owner.stealer();
+        // past MIN_CAPACITY so the buffer grows with items in it
+        for i in 0..100 {
+            owner.push(i);
+        }
+        assert_eq!(owner.len(), 100);
+        assert_eq!(stealer.steal(), Some(0));
+        assert_eq!(owner.pop(), Some(99));
+        let rest: Vec<usize> = std::iter::from_fn(|| stealer.steal()).collect();
+        assert_eq!(rest, (1..99).collect::<Vec<_>>());
+        assert_eq!(owner.pop(), None);
+        assert!(stealer.is_empty());
+    }
+
+    #[test]
+    fn items_left_behind_are_dropped_with_the_deque() {
+        let item = Arc::new(());
+        let owner = Owner::new();
+        for _ in 0..50 {
+            owner.push(Arc::clone(&item));
+        }
+        owner.pop();
+        drop(owner);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn every_item_is_taken_exactly_once_under_contention() {
+        const ITEMS: usize = 20_000;
+        let owner = Owner::new();
+        let done = Arc::new(AtomicBool::new(false));
+        let thieves: Vec<_> = (0..3)
+            .map(|_| {
+                let stealer = owner.stealer();
+                let done = Arc::clone(&done);
+                thread::spawn(move || {
+                    let mut taken = Vec::new();
+                    while !done.load(Ordering::Acquire) || !stealer.is_empty() {
+                        match stealer.steal() {
+                            Some(item) => taken.push(item),
+                            None => thread::yield_now(),
+                        }
+                    }
+                    taken
+                })
+            })
+            .collect();
+        // bursts larger than MIN_CAPACITY keep the buffer growing while
+        // the thieves are reading from it
+        let mut taken = Vec::new();
+        let mut next = 0;
+        while next < ITEMS {
+            for _ in 0..100.min(ITEMS - next) {
+                owner.push(next);
+                next += 1;
+            }
+            taken.extend((0..40).filter_map(|_| owner.pop()));
+            thread::yield_now();
+        }
+        taken.extend(std::iter::from_fn(|| owner.pop()));
+        done.store(true, Ordering::Release);
+        for thief in thieves {
+            taken.extend(thief.join().unwrap());
+        }
+        taken.sort_unstable();
+        assert_eq!(taken, (0..ITEMS).collect::<Vec<_>>());
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use loom::thread;
+
+    use super::*;
+
+    // the owner and a thief race for the last item, exactly one wins it
+    #[test]
+    fn pop_and_steal_race_for_the_last_item() {
+        loom::model(|| {
+            let owner = Owner::new();
+            let stealer = owner.stealer();
+            owner.push(1);
+            let thief = thread::spawn(move || stealer.steal());
+            let popped = owner.pop();
+            let stolen = thief.join().unwrap();
+            assert!(popped.is_some() != stolen.is_some());
+            assert_eq!(popped.or(stolen), Some(1));
+            assert_eq!(owner.pop(), None);
+        });
+    }
+
+    // a push racing a steal never loses or duplicates an item
+    #[test]
+    fn push_pop_and_steal_take_every_item_once() {
+        loom::model(|| {
+            let owner = Owner::new();
+            let stealer = owner.stealer();
+            owner.push(1);
+            let thief = thread::spawn(move || stealer.steal());
+            owner.push(2);
+            let mut taken: Vec<i32> =
+                std::iter::from_fn(|| owner.pop()).collect();
+            taken.extend(thief.join().unwrap());
+            taken.sort_unstable();
+            assert_eq!(taken, [1, 2]);
+        });
+    }
+}
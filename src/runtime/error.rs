@@ -0,0 +1,31 @@
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+
+// a job that panicked, along with the input it was given
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobError<T> {
+    pub input: T,
+    pub message: String,
+}
+
+impl<T> JobError<T> {
+    pub fn from_panic(input: T, payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "unknown panic payload".to_string(),
+            },
+        };
+        Self { input, message }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Display for JobError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "job panicked on {:?}: {}", self.input, self.message)
+    }
+}
+
+impl<T: fmt::Debug> Error for JobError<T> {}
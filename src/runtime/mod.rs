@@ -2,7 +2,14 @@
 pub mod deque;
 pub mod batches;
 pub mod workpool;
+pub mod progress;
+pub mod cancel;
+pub mod error;
+#[allow(clippy::module_inception)]
 pub mod runtime;
 
 // flatten
 pub use runtime::*;
+pub use progress::Progress;
+pub use cancel::CancelToken;
+pub use error::JobError;
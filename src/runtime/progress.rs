@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+// a snapshot of a run, taken each time a batch comes back
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Progress {
+    pub completed: usize,
+    pub total: usize,
+    // items in the completed batches and in the whole run
+    pub items: usize,
+    pub total_items: usize,
+    pub elapsed: Duration,
+    // set on the last snapshot of a run that was stopped early, nothing
+    // more comes back after it
+    pub stopped: bool,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f64 {
+        match self.total_items {
+            0 => 1.0,
+            n => self.items as f64 / n as f64,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.stopped || self.completed == self.total
+    }
+
+    // items per second so far
+    pub fn throughput(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.items as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    // remaining time at the throughput so far, None until it is known and
+    // zero once nothing more is coming
+    pub fn eta(&self) -> Option<Duration> {
+        if self.is_done() {
+            return Some(Duration::ZERO);
+        }
+        let rate = self.throughput();
+        if rate <= 0.0 {
+            return None;
+        }
+        let remaining = self.total_items.saturating_sub(self.items);
+        Some(Duration::from_secs_f64(remaining as f64 / rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(completed: usize, items: usize, millis: u64) -> Progress {
+        Progress {
+            completed,
+            total: 4,
+            items,
+            total_items: 100,
+            elapsed: Duration::from_millis(millis),
+            stopped: false,
+        }
+    }
+
+    #[test]
+    fn nothing_completed_has_no_rate_or_eta() {
+        for millis in [0, 500] {
+            let start = progress(0, 0, millis);
+            assert_eq!(start.fraction(), 0.0);
+            assert_eq!(start.throughput(), 0.0);
+            assert_eq!(start.eta(), None);
+            assert!(!start.is_done());
+        }
+    }
+
+    #[test]
+    fn partway_the_rate_gives_the_eta() {
+        let partway = progress(1, 25, 500);
+        assert_eq!(partway.fraction(), 0.25);
+        assert_eq!(partway.throughput(), 50.0);
+        assert_eq!(partway.eta(), Some(Duration::from_millis(1500)));
+        assert!(!partway.is_done());
+        // completed before any time was measured
+        assert_eq!(progress(1, 25, 0).eta(), None);
+    }
+
+    #[test]
+    fn finished_and_stopped_runs_are_done() {
+        let finished = progress(4, 100, 2000);
+        assert_eq!(finished.fraction(), 1.0);
+        assert_eq!(finished.throughput(), 50.0);
+        assert_eq!(finished.eta(), Some(Duration::ZERO));
+        assert!(finished.is_done());
+        let stopped = Progress {
+            stopped: true,
+            ..progress(2, 40, 0)
+        };
+        assert_eq!(stopped.fraction(), 0.4);
+        assert_eq!(stopped.eta(), Some(Duration::ZERO));
+        assert!(stopped.is_done());
+        // an empty run is done from the start
+        let empty = Progress {
+            total: 0,
+            total_items: 0,
+            ..progress(0, 0, 0)
+        };
+        assert_eq!(empty.fraction(), 1.0);
+        assert_eq!(empty.eta(), Some(Duration::ZERO));
+        assert!(empty.is_done());
+    }
+}
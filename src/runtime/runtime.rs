@@ -1,100 +1,291 @@
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, RwLock};
 use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use super::batches::{WorkBatch, OutputBatch, Batcher};
+use super::batches::{Batcher, OutputBatch, WorkBatch};
 use super::workpool::WorkPool;
+use super::CancelToken;
+use super::JobError;
+use super::Progress;
 
+use std::fmt;
 use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
 
 pub trait Job<T, U> {
     fn run(&self, input: &T) -> U;
 }
 
+// pushes and pops come from the queue's own worker, or the manager while
+// the workers are idle, steals from any other worker
 pub trait WorkQueue<T> {
     fn new(id: usize, batches: Vec<WorkBatch<T>>) -> Self;
 
     fn empty(&self) -> bool;
 
-    fn push(&mut self, batch: WorkBatch<T>);
+    fn push(&self, batch: WorkBatch<T>);
 
-    fn pop(&mut self) -> Option<WorkBatch<T>>;
+    fn pop(&self) -> Option<WorkBatch<T>>;
+
+    // a batch taken on behalf of another worker, queues that do not share
+    // their work return None
+    fn steal(&self) -> Option<WorkBatch<T>> {
+        None
+    }
 }
 
-pub trait WorkConfig: Send + Sync + 'static {
-    const THREAD_COUNT: usize;
-    const BATCH_COUNT: usize;
+// batches handed out per worker by default
+pub const BATCHES_PER_THREAD: usize = 64;
+
+// worker count to use when the caller has no preference
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
 
-    type Input: Send + Sync + 'static;
+pub trait WorkConfig: Send + Sync + 'static {
+    // cloned into the error when a job panics on it
+    type Input: Clone + fmt::Debug + Send + Sync + 'static;
     type Output: Send + Sync + 'static;
-    type Job: Job<Self::Input, Self::Output> + Send + Sync + 'static; 
-    type Queue: WorkQueue<Self::Input> + Send + Sync + 'static; 
+    type Job: Job<Self::Input, Self::Output> + Send + Sync + 'static;
+    type Queue: WorkQueue<Self::Input> + Send + Sync + 'static;
 }
 
+// a persistent pool of worker threads, each call to execute hands out a
+// new job and its data and join collects the results, one run at a time
+type BatchResult<WC> = Result<
+    OutputBatch<<WC as WorkConfig>::Output>,
+    JobError<<WC as WorkConfig>::Input>,
+>;
+
 pub struct Manager<WC: WorkConfig> {
     workers: Vec<Arc<Worker<WC>>>,
-    collector: Receiver<OutputBatch<WC::Output>>,
+    handles: Vec<JoinHandle<()>>,
+    // batches the data of each run is split into
+    batch_count: usize,
+    work_pool: WorkPool<WC::Input, WC::Queue>,
+    collector: Receiver<BatchResult<WC>>,
+    // batches of the current run that have not been collected yet
+    pending: usize,
+    // batches, items and start time of the current run
+    total: usize,
+    total_items: usize,
+    started: Instant,
 }
 
 impl<WC> Manager<WC>
 where
     WC: WorkConfig,
 {
-    pub fn new(
-        job: &Arc<WC::Job>,
-        data: Vec<WC::Input>,
-    ) -> Self {
+    // spawns the workers, which stay parked until there is work, see
+    // default_threads and BATCHES_PER_THREAD for the usual counts
+    pub fn new(thread_count: usize, batch_count: usize) -> Self {
+        assert!(thread_count > 0, "at least one worker thread is needed");
+        assert!(batch_count > 0, "at least one batch is needed");
         let (worker_tx, collector) = channel();
-        let mut workers = Vec::with_capacity(WC::THREAD_COUNT);
-        let batches = Batcher::new(data).create_batches(WC::BATCH_COUNT);
-        let work_pool = WorkPool::new(WC::THREAD_COUNT, batches);
+        let work_pool = WorkPool::new(thread_count);
+        let mut workers = Vec::with_capacity(thread_count);
+        let mut handles = Vec::with_capacity(thread_count);
         for (idx, queue) in work_pool.pool.iter().enumerate() {
-            let worker = Arc::new(Worker::new(
-                idx, job, &queue, &worker_tx
-            ));
+            let peers = work_pool.pool.clone();
+            let worker = Arc::new(Worker::new(idx, queue, peers, &worker_tx));
+            let runner = Arc::clone(&worker);
+            let handle = thread::Builder::new()
+                .name(format!("lumen-worker-{idx}"))
+                .spawn(move || runner.run())
+                .expect("failed to spawn a worker thread");
             workers.push(worker);
+            handles.push(handle);
         }
         Self {
             workers,
+            handles,
+            batch_count,
+            work_pool,
             collector,
+            pending: 0,
+            total: 0,
+            total_items: 0,
+            started: Instant::now(),
         }
     }
 
-    pub fn execute(&self) {
+    pub fn thread_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn batch_count(&self) -> usize {
+        self.batch_count
+    }
+
+    // the data is split into batch_count batches, or one per item when
+    // there are fewer items, shared out between the workers. neither count
+    // has to divide the other
+    pub fn execute(&mut self, job: &Arc<WC::Job>, data: Vec<WC::Input>) {
+        assert!(self.pending == 0, "the previous run was never joined");
+        for worker in &self.workers {
+            *worker.job.write().unwrap() = Some(Arc::clone(job));
+        }
+        self.total_items = data.len();
+        self.started = Instant::now();
+        let batches = Batcher::new(data).create_batches(self.batch_count);
+        self.pending = batches.len();
+        self.total = batches.len();
+        self.work_pool.distribute(batches);
+        for handle in &self.handles {
+            handle.thread().unpark();
+        }
+    }
+
+    pub fn join(&mut self) -> Result<Vec<WC::Output>, JobError<WC::Input>> {
+        self.join_with_progress(|_| {})
+    }
+
+    // like join, calling back on this thread after every batch that comes
+    // back
+    pub fn join_with_progress<F>(
+        &mut self,
+        on_progress: F,
+    ) -> Result<Vec<WC::Output>, JobError<WC::Input>>
+    where
+        F: FnMut(&Progress),
+    {
+        let never = CancelToken::new();
+        let result = self.join_until(&never, None, on_progress)?;
+        Ok(result
+            .into_iter()
+            .map(|item| item.expect("a run that was never stopped"))
+            .collect())
+    }
+
+    // like join_with_progress, but once the token is cancelled or the
+    // deadline passes the batches nobody started are dropped, batches in
+    // flight still finish and the dropped items come back as None. a job
+    // that panics stops the run the same way and the first panic is
+    // returned once the batches in flight are back. a stopped run ends
+    // with one more progress call, marked as stopped
+    pub fn join_until<F>(
+        &mut self,
+        cancel: &CancelToken,
+        deadline: Option<Instant>,
+        mut on_progress: F,
+    ) -> Result<Vec<Option<WC::Output>>, JobError<WC::Input>>
+    where
+        F: FnMut(&Progress),
+    {
+        let mut result = Vec::with_capacity(self.pending);
+        let (mut completed, mut items) = (0, 0);
+        let mut stopped = false;
+        let mut failure = None;
+        while self.pending > 0 {
+            let failed = failure.is_some();
+            if !stopped && (failed || stop_requested(cancel, deadline)) {
+                stopped = true;
+                for batch in self.work_pool.drain() {
+                    let skipped =
+                        batch.items.iter().map(|_| None).collect::<Vec<_>>();
+                    result.push((batch.id, skipped));
+                    self.pending -= 1;
+                }
+                continue;
+            }
+            let output_batch = match stopped {
+                true => self.collector.recv().ok(),
+                false => {
+                    let wait = poll_interval(deadline);
+                    self.collector.recv_timeout(wait).ok()
+                }
+            };
+            let output_batch = match output_batch {
+                Some(Ok(output_batch)) => output_batch,
+                Some(Err(err)) => {
+                    self.pending -= 1;
+                    failure.get_or_insert(err);
+                    continue;
+                }
+                None => continue,
+            };
+            completed += 1;
+            items += output_batch.items.len();
+            let id = output_batch.id;
+            result.push((id, output_batch.into_iter().map(Some).collect()));
+            self.pending -= 1;
+            on_progress(&self.progress(completed, items, false));
+        }
+        if stopped {
+            on_progress(&self.progress(completed, items, true));
+        }
+        // drop the job so whatever it holds is not kept alive between runs
         for worker in &self.workers {
-            let worker = Arc::clone(&worker);
-            thread::spawn(move || {
-                worker.run();
-            });
+            *worker.job.write().unwrap() = None;
+        }
+        if let Some(err) = failure {
+            return Err(err);
         }
+        result.sort_by_key(|(id, _)| *id);
+        Ok(result.into_iter().flat_map(|(_, batch)| batch).collect())
     }
 
-    pub fn join(&self) -> Vec<WC::Output> {
-        let mut result = Vec::with_capacity(WC::BATCH_COUNT);
-        for _ in 0..WC::BATCH_COUNT {
-            let output_batch = self.collector.recv().unwrap();
-            result.push(output_batch);
+    fn progress(
+        &self,
+        completed: usize,
+        items: usize,
+        stopped: bool,
+    ) -> Progress {
+        Progress {
+            completed,
+            total: self.total,
+            items,
+            total_items: self.total_items,
+            elapsed: self.started.elapsed(),
+            stopped,
         }
+    }
+}
+
+fn stop_requested(cancel: &CancelToken, deadline: Option<Instant>) -> bool {
+    cancel.is_cancelled() || deadline.is_some_and(|d| Instant::now() >= d)
+}
+
+// how long to block on results before checking for a stop again
+fn poll_interval(deadline: Option<Instant>) -> Duration {
+    const POLL: Duration = Duration::from_millis(10);
+    match deadline {
+        Some(d) => d.saturating_duration_since(Instant::now()).min(POLL),
+        None => POLL,
+    }
+}
+
+impl<WC> Drop for Manager<WC>
+where
+    WC: WorkConfig,
+{
+    // workers finish the batch in hand and exit
+    fn drop(&mut self) {
         for worker in &self.workers {
-            let mut status = worker.status.write().unwrap();
-            *status = WorkStatus::Completed;
+            *worker.status.write().unwrap() = WorkStatus::Shutdown;
+        }
+        for handle in self.handles.drain(..) {
+            handle.thread().unpark();
+            let _ = handle.join();
         }
-        result.sort_by_key(|b| b.id);
-        result.into_iter().flat_map(|b| b.into_iter()).collect()
     }
 }
 
 enum WorkStatus {
-    Working,
-    Completed,
+    Running,
+    Shutdown,
 }
 
 struct Worker<WC: WorkConfig> {
-    _id: usize,
-    job: Arc<WC::Job>,
-    status: Arc<RwLock<WorkStatus>>,
-    work: Arc<RwLock<WC::Queue>>,
-    output: Sender<OutputBatch<WC::Output>>,
+    id: usize,
+    job: RwLock<Option<Arc<WC::Job>>>,
+    status: RwLock<WorkStatus>,
+    work: Arc<WC::Queue>,
+    // every queue in the pool, including this worker's own
+    peers: Vec<Arc<WC::Queue>>,
+    output: Sender<BatchResult<WC>>,
     _phantom: PhantomData<WC::Input>,
 }
 
@@ -103,42 +294,212 @@ where
     WC: WorkConfig,
 {
     pub fn new(
-        _id: usize,
-        job: &Arc<WC::Job>,
-        work_queue: &Arc<RwLock<WC::Queue>>,
-        outgoing: &Sender<OutputBatch<WC::Output>>,
+        id: usize,
+        work_queue: &Arc<WC::Queue>,
+        peers: Vec<Arc<WC::Queue>>,
+        outgoing: &Sender<BatchResult<WC>>,
     ) -> Self {
         Self {
-            _id: _id,
-            job: Arc::clone(job),
-            status: Arc::new(RwLock::new(WorkStatus::Working)),
+            id,
+            job: RwLock::new(None),
+            status: RwLock::new(WorkStatus::Running),
             work: Arc::clone(work_queue),
+            peers,
             output: outgoing.clone(),
             _phantom: PhantomData,
         }
     }
 
-    pub fn get_work(&self) -> Option<WorkBatch<WC::Input>> {
-        self.work.write().unwrap().pop()
+    // own work first, then a steal from each peer in turn starting at a
+    // random one so idle workers spread out over the victims
+    pub fn get_work(&self, rng: &mut u64) -> Option<WorkBatch<WC::Input>> {
+        if let Some(batch) = self.work.pop() {
+            return Some(batch);
+        }
+        let start = xorshift(rng) as usize;
+        let count = self.peers.len();
+        (0..count)
+            .map(|i| (start + i) % count)
+            .filter(|&victim| victim != self.id)
+            .find_map(|victim| self.peers[victim].steal())
     }
 
+    // a panicking job fails the whole batch, the worker itself carries on
     pub fn process_batch(
-        &self, input_batch: &WorkBatch<WC::Input>
-    ) -> OutputBatch<WC::Output> {
+        &self,
+        input_batch: &WorkBatch<WC::Input>,
+    ) -> BatchResult<WC> {
+        let job = self.job.read().unwrap().clone();
+        let job = job.expect("work was queued without a job");
         let mut output_batch = OutputBatch::new(input_batch.id);
         for item in &input_batch.items {
-            let output_item = self.job.run(&item); 
+            let output_item =
+                panic::catch_unwind(AssertUnwindSafe(|| job.run(item)))
+                    .map_err(|payload| {
+                        JobError::from_panic(item.clone(), payload)
+                    })?;
             output_batch.items.push(output_item);
         }
-        output_batch
+        Ok(output_batch)
     }
 
+    // parks whenever its queue runs dry, the manager unparks it when new
+    // work arrives or it is time to exit
     pub fn run(&self) {
+        let mut rng = 0x9e3779b97f4a7c15 ^ (self.id as u64 + 1);
         loop {
-            if let Some(batch) = self.get_work() {
-                let output_batch = self.process_batch(&batch);
-                let _ = self.output.send(output_batch);
+            if let WorkStatus::Shutdown = *self.status.read().unwrap() {
+                return;
+            }
+            match self.get_work(&mut rng) {
+                Some(batch) => {
+                    let output_batch = self.process_batch(&batch);
+                    let _ = self.output.send(output_batch);
+                }
+                None => thread::park(),
             }
         }
     }
 }
+
+// cheap victim selection that leaves the rendering random numbers alone
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::workpool::StealingQueue;
+
+    struct Square;
+
+    impl Job<usize, usize> for Square {
+        fn run(&self, input: &usize) -> usize {
+            input * input
+        }
+    }
+
+    struct SquareConfig;
+
+    impl WorkConfig for SquareConfig {
+        type Input = usize;
+        type Output = usize;
+        type Job = Square;
+        type Queue = StealingQueue<usize>;
+    }
+
+    // squares, except for one input it cannot stand
+    struct Picky(usize);
+
+    impl Job<usize, usize> for Picky {
+        fn run(&self, input: &usize) -> usize {
+            if *input == self.0 {
+                panic!("refusing {input}");
+            }
+            input * input
+        }
+    }
+
+    struct PickyConfig;
+
+    impl WorkConfig for PickyConfig {
+        type Input = usize;
+        type Output = usize;
+        type Job = Picky;
+        type Queue = StealingQueue<usize>;
+    }
+
+    fn squares(len: usize) -> Vec<usize> {
+        (0..len).map(|i| i * i).collect()
+    }
+
+    #[test]
+    fn uneven_runs_come_back_whole_and_in_order() {
+        let mut manager = Manager::<SquareConfig>::new(3, 512);
+        for len in [1000 * 333, 1009, 511, 0] {
+            manager.execute(&Arc::new(Square), (0..len).collect());
+            assert_eq!(manager.join().unwrap(), squares(len), "{len} items");
+        }
+    }
+
+    // workers over a pool where the first queue holds every batch
+    fn lopsided_pool(batches: usize) -> Vec<Arc<Worker<SquareConfig>>> {
+        let pool = WorkPool::<usize, StealingQueue<usize>>::new(4);
+        for id in 0..batches {
+            pool.pool[0].push(WorkBatch::new(id, vec![id]));
+        }
+        let (tx, _) = channel();
+        (0..4)
+            .map(|i| {
+                let peers = pool.pool.clone();
+                Arc::new(Worker::new(i, &pool.pool[i], peers, &tx))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn idle_workers_steal_from_a_busy_queue() {
+        let workers = lopsided_pool(100);
+        let mut rng = 1;
+        let stolen: Vec<usize> =
+            std::iter::from_fn(|| workers[2].get_work(&mut rng))
+                .map(|batch| batch.id)
+                .collect();
+        assert_eq!(stolen, (0..100).collect::<Vec<_>>());
+
+        let workers = lopsided_pool(1000);
+        let threads: Vec<_> = workers
+            .into_iter()
+            .map(|worker| {
+                thread::spawn(move || {
+                    let mut rng = worker.id as u64 + 1;
+                    std::iter::from_fn(|| worker.get_work(&mut rng))
+                        .map(|batch| batch.id)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut ids: Vec<usize> = threads
+            .into_iter()
+            .flat_map(|thread| thread.join().unwrap())
+            .collect();
+        ids.sort_unstable();
+        assert_eq!(ids, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn more_threads_than_batches() {
+        let mut manager = Manager::<SquareConfig>::new(8, 3);
+        manager.execute(&Arc::new(Square), (0..10).collect());
+        assert_eq!(manager.total, 3);
+        assert_eq!(manager.join().unwrap(), squares(10));
+        manager.execute(&Arc::new(Square), (0..2).collect());
+        assert_eq!(manager.total, 2);
+        assert_eq!(manager.join().unwrap(), squares(2));
+    }
+
+    #[test]
+    fn a_panicking_job_fails_the_run_and_the_pool_carries_on() {
+        let mut manager = Manager::<PickyConfig>::new(4, 64);
+        let job = Arc::new(Picky(613));
+        manager.execute(&job, (0..1000).collect());
+        let err = manager.join().unwrap_err();
+        assert_eq!(
+            err,
+            JobError {
+                input: 613,
+                message: "refusing 613".to_string(),
+            }
+        );
+        assert_eq!(err.to_string(), "job panicked on 613: refusing 613");
+        // every worker is still there for the next run
+        manager.execute(&job, (0..600).collect());
+        assert_eq!(manager.join().unwrap(), squares(600));
+        manager.execute(&Arc::new(Picky(0)), (0..1000).collect());
+        assert_eq!(manager.join().unwrap_err().input, 0);
+    }
+}
@@ -1,40 +1,78 @@
-use std::sync::{Arc, RwLock};
 use std::collections::VecDeque;
 use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
 
 use super::batches::WorkBatch;
+use super::deque::{Owner, Stealer};
 use super::WorkQueue;
 
+// a locked fifo of batches that only its own worker takes from
 pub struct BaseQueue<T> {
     pub id: usize,
-    pub items: usize,
-    work: VecDeque<WorkBatch<T>>,
+    work: Mutex<VecDeque<WorkBatch<T>>>,
 }
 
 impl<T> WorkQueue<T> for BaseQueue<T> {
     fn new(id: usize, batches: Vec<WorkBatch<T>>) -> Self {
         Self {
-            id: id,
-            items: batches.len(),
-            work: VecDeque::from(batches),
+            id,
+            work: Mutex::new(VecDeque::from(batches)),
         }
     }
 
     fn empty(&self) -> bool {
-        self.items <= 0
+        self.work.lock().unwrap().is_empty()
     }
-    
-    fn push(&mut self, batch: WorkBatch<T>) {
-        self.work.push_front(batch);
+
+    fn push(&self, batch: WorkBatch<T>) {
+        self.work.lock().unwrap().push_front(batch);
     }
 
-    fn pop(&mut self) -> Option<WorkBatch<T>> {
-        self.work.pop_back()
+    fn pop(&self) -> Option<WorkBatch<T>> {
+        self.work.lock().unwrap().pop_back()
+    }
+}
+
+// a Chase-Lev deque that idle workers can steal batches from, the owner
+// side is locked only so the manager can refill it between runs
+pub struct StealingQueue<T> {
+    pub id: usize,
+    owner: Mutex<Owner<WorkBatch<T>>>,
+    stealer: Stealer<WorkBatch<T>>,
+}
+
+impl<T> WorkQueue<T> for StealingQueue<T> {
+    fn new(id: usize, batches: Vec<WorkBatch<T>>) -> Self {
+        let owner = Owner::new();
+        for batch in batches {
+            owner.push(batch);
+        }
+        Self {
+            id,
+            stealer: owner.stealer(),
+            owner: Mutex::new(owner),
+        }
+    }
+
+    fn empty(&self) -> bool {
+        self.stealer.is_empty()
+    }
+
+    fn push(&self, batch: WorkBatch<T>) {
+        self.owner.lock().unwrap().push(batch);
+    }
+
+    fn pop(&self) -> Option<WorkBatch<T>> {
+        self.owner.lock().unwrap().pop()
+    }
+
+    fn steal(&self) -> Option<WorkBatch<T>> {
+        self.stealer.steal()
     }
 }
 
 pub struct WorkPool<T, Q> {
-    pub pool: Vec<Arc<RwLock<Q>>>,
+    pub pool: Vec<Arc<Q>>,
     _phantom: PhantomData<T>,
 }
 
@@ -42,17 +80,83 @@ impl<T, Q> WorkPool<T, Q>
 where
     Q: WorkQueue<T>,
 {
-    pub fn new(
-        worker_count: usize,
-        mut batches: VecDeque<WorkBatch<T>>
-    ) -> Self {
+    // one empty queue per worker, filled by distribute
+    pub fn new(worker_count: usize) -> Self {
+        let pool = (0..worker_count)
+            .map(|i| Arc::new(Q::new(i, Vec::new())))
+            .collect();
+        Self {
+            pool,
+            _phantom: PhantomData,
+        }
+    }
+
+    // the first batches.len() % worker_count queues take one batch more
+    // than the rest, so no batch is left out
+    pub fn distribute(&self, mut batches: VecDeque<WorkBatch<T>>) {
+        let worker_count = self.pool.len();
         let batch_count = batches.len() / worker_count;
-        let mut pool = Vec::with_capacity(worker_count);
-        for i in 0..worker_count {
-            let queue_batches = batches.drain(..batch_count).collect();
-            let queue = Arc::new(RwLock::new(Q::new(i, queue_batches)));
-            pool.push(queue);
+        let remainder = batches.len() % worker_count;
+        for (i, queue) in self.pool.iter().enumerate() {
+            let count = batch_count + usize::from(i < remainder);
+            for batch in batches.drain(..count) {
+                queue.push(batch);
+            }
         }
-        Self { pool, _phantom: PhantomData, }
+    }
+
+    // takes back every batch still waiting in a queue
+    pub fn drain(&self) -> Vec<WorkBatch<T>> {
+        let mut batches = Vec::new();
+        for queue in &self.pool {
+            while let Some(batch) = queue.pop() {
+                batches.push(batch);
+            }
+        }
+        batches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batches(count: usize) -> VecDeque<WorkBatch<usize>> {
+        (0..count).map(|id| WorkBatch::new(id, vec![id])).collect()
+    }
+
+    fn queue_sizes<Q: WorkQueue<usize>>(
+        pool: &WorkPool<usize, Q>,
+    ) -> Vec<usize> {
+        pool.pool
+            .iter()
+            .map(|queue| {
+                let mut count = 0;
+                while queue.pop().is_some() {
+                    count += 1;
+                }
+                count
+            })
+            .collect()
+    }
+
+    #[test]
+    fn uneven_batches_are_spread_over_every_queue() {
+        let pool = WorkPool::<usize, BaseQueue<usize>>::new(4);
+        pool.distribute(batches(10));
+        assert_eq!(queue_sizes(&pool), [3, 3, 2, 2]);
+    }
+
+    #[test]
+    fn more_queues_than_batches_leaves_some_empty() {
+        let pool = WorkPool::<usize, StealingQueue<usize>>::new(8);
+        pool.distribute(batches(3));
+        assert_eq!(queue_sizes(&pool), [1, 1, 1, 0, 0, 0, 0, 0]);
+        pool.distribute(batches(3));
+        let mut ids: Vec<usize> =
+            pool.drain().into_iter().map(|batch| batch.id).collect();
+        ids.sort();
+        assert_eq!(ids, [0, 1, 2]);
+        assert!(pool.pool.iter().all(|queue| queue.empty()));
     }
 }
@@ -0,0 +1,258 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::loaders::LoadError;
+use crate::math::*;
+
+use super::TextureValue;
+
+// texels are stored as linear radiance, decoded from srgb on load
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<Vec3>,
+    // file the texels were loaded from, if any
+    pub path: Option<PathBuf>,
+}
+
+fn srgb_to_linear(c: f64) -> f64 {
+    match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, data: Vec<Vec3>) -> Self {
+        assert_eq!(width * height, data.len());
+        Self {
+            width,
+            height,
+            data,
+            path: None,
+        }
+    }
+
+    // picks the decoder from the file extension, ppm or png
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let io_error = |err| LoadError::Io(path.to_path_buf(), err);
+        let mut reader = BufReader::new(File::open(path).map_err(io_error)?);
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        let mut image = match extension.as_deref() {
+            Some("png") => Self::from_png(path, reader),
+            Some("ppm") => {
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes).map_err(io_error)?;
+                Self::from_ppm(path, &bytes)
+            }
+            _ => Err(LoadError::format(
+                path,
+                "unsupported image format, expected .png or .ppm",
+            )),
+        }?;
+        image.path = Some(path.to_path_buf());
+        Ok(image)
+    }
+
+    pub fn from_png<R>(path: &Path, reader: R) -> Result<Self, LoadError>
+    where
+        R: std::io::BufRead + std::io::Seek,
+    {
+        let format_error = |err: png::DecodingError| {
+            LoadError::format(path, &format!("invalid png: {}", err))
+        };
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut png = decoder.read_info().map_err(format_error)?;
+        let size = png.output_buffer_size().ok_or_else(|| {
+            LoadError::format(path, "png dimensions are too large")
+        })?;
+        let mut buffer = vec![0; size];
+        let info = png.next_frame(&mut buffer).map_err(format_error)?;
+        let channels = info.color_type.samples();
+        let sixteen = info.bit_depth == png::BitDepth::Sixteen;
+        let max = if sixteen { 65535.0 } else { 255.0 };
+        let sample = |row: &[u8], i: usize| match sixteen {
+            true => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]) as f64,
+            false => row[i] as f64,
+        };
+        let (width, height) = (info.width as usize, info.height as usize);
+        let mut data = Vec::with_capacity(width * height);
+        for row in buffer.chunks(info.line_size).take(height) {
+            for x in 0..width {
+                let texel = |c: usize| {
+                    srgb_to_linear(sample(row, x * channels + c) / max)
+                };
+                data.push(match channels {
+                    1 | 2 => Vec3::new(texel(0), texel(0), texel(0)),
+                    _ => Vec3::new(texel(0), texel(1), texel(2)),
+                });
+            }
+        }
+        Ok(Self::new(width, height, data))
+    }
+
+    // binary (P6) and ascii (P3) portable pixmaps
+    pub fn from_ppm(path: &Path, bytes: &[u8]) -> Result<Self, LoadError> {
+        let mut pos = 0;
+        let mut header = Vec::with_capacity(4);
+        while header.len() < 4 {
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if pos < bytes.len() && bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            let start = pos;
+            while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                return Err(LoadError::format(path, "truncated ppm header"));
+            }
+            header.push(String::from_utf8_lossy(&bytes[start..pos]));
+        }
+        let number = |s: &str| {
+            s.parse::<usize>().map_err(|_| {
+                LoadError::format(path, &format!("invalid ppm header '{}'", s))
+            })
+        };
+        let (width, height) = (number(&header[1])?, number(&header[2])?);
+        let max = number(&header[3])?;
+        if max == 0 || max > 65535 {
+            return Err(LoadError::format(path, "invalid ppm maximum value"));
+        }
+        if width == 0 || height == 0 {
+            return Err(LoadError::format(path, "empty ppm image"));
+        }
+        let count = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(3))
+            .ok_or_else(|| {
+                LoadError::format(path, "ppm dimensions are too large")
+            })?;
+        let samples: Vec<usize> = match header[0].as_ref() {
+            "P6" => {
+                // exactly one whitespace byte separates header and data
+                let data = &bytes[(pos + 1).min(bytes.len())..];
+                let size = if max < 256 { 1 } else { 2 };
+                if count.checked_mul(size).is_none_or(|n| data.len() < n) {
+                    return Err(LoadError::format(path, "truncated ppm data"));
+                }
+                (0..count)
+                    .map(|i| match size {
+                        1 => data[i] as usize,
+                        _ => u16::from_be_bytes([data[2 * i], data[2 * i + 1]])
+                            as usize,
+                    })
+                    .collect()
+            }
+            "P3" => {
+                let text = String::from_utf8_lossy(&bytes[pos..]);
+                let samples = text
+                    .split_whitespace()
+                    .take(count)
+                    .map(number)
+                    .collect::<Result<Vec<usize>, LoadError>>()?;
+                if samples.len() < count {
+                    return Err(LoadError::format(path, "truncated ppm data"));
+                }
+                samples
+            }
+            magic => {
+                return Err(LoadError::format(
+                    path,
+                    &format!("unsupported ppm type '{}'", magic),
+                ))
+            }
+        };
+        let data = samples
+            .chunks(3)
+            .map(|c| {
+                let channel = |s: usize| srgb_to_linear(s as f64 / max as f64);
+                Vec3::new(channel(c[0]), channel(c[1]), channel(c[2]))
+            })
+            .collect();
+        Ok(Self::new(width, height, data))
+    }
+}
+
+impl TextureValue for ImageTexture {
+    // nearest texel, with v = 0 at the bottom of the image
+    fn value(&self, u: f64, v: f64, _p: Vec3) -> Vec3 {
+        if self.data.is_empty() {
+            return Vec3::new(0.0, 1.0, 1.0);
+        }
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0);
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.data[j * self.width + i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_decodes_to_linear() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert_eq!(srgb_to_linear(1.0), 1.0);
+        // the two pieces of the curve meet at the threshold
+        let below = srgb_to_linear(0.04045);
+        let above = ((0.04045f64 + 0.055) / 1.055).powf(2.4);
+        assert!((below - above).abs() < 1e-7);
+        assert!((srgb_to_linear(0.5) - 0.214041).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ppm_texels_are_linear_with_v_up() {
+        // top row black and white, bottom row mid gray and red
+        let ppm = b"P3\n2 2\n255\n0 0 0 255 255 255\n128 128 128 255 0 0\n";
+        let image = ImageTexture::from_ppm(Path::new("t.ppm"), ppm).unwrap();
+        let gray = srgb_to_linear(128.0 / 255.0);
+        assert!((gray - 0.2158605).abs() < 1e-6);
+        let at = |u, v| image.value(u, v, Vec3::default());
+        assert_eq!(at(0.25, 0.75), Vec3::default());
+        assert_eq!(at(0.75, 0.75), Vec3::new(1.0, 1.0, 1.0));
+        assert_eq!(at(0.25, 0.25), Vec3::new(gray, gray, gray));
+        assert_eq!(at(0.75, 0.25), Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn bad_ppm_dimensions_are_rejected() {
+        let error = |ppm: &[u8]| {
+            let err = ImageTexture::from_ppm(Path::new("t.ppm"), ppm);
+            err.unwrap_err().to_string()
+        };
+        let huge = format!("P6 {} {} 255\n", 1u64 << 32, (1u64 << 32) + 1);
+        assert!(error(huge.as_bytes()).ends_with("too large"));
+        let wide = format!("P6 {} 1 255\n", usize::MAX / 3);
+        assert!(error(wide.as_bytes()).ends_with("truncated ppm data"));
+        let wide = format!("P3 {} 1 255\n0 0 0\n", usize::MAX / 3);
+        assert!(error(wide.as_bytes()).ends_with("truncated ppm data"));
+        assert!(error(b"P3 0 5 255\n").ends_with("empty ppm image"));
+        assert!(error(b"P6 5 0 255\n").ends_with("empty ppm image"));
+    }
+
+    #[test]
+    fn binary_ppm_matches_ascii() {
+        let ascii = b"P3 1 1 255 10 128 250";
+        let mut binary = b"P6\n1 1\n255\n".to_vec();
+        binary.extend([10, 128, 250]);
+        let path = Path::new("t.ppm");
+        let a = ImageTexture::from_ppm(path, ascii).unwrap();
+        let b = ImageTexture::from_ppm(path, &binary).unwrap();
+        assert_eq!(a.data, b.data);
+    }
+}
@@ -0,0 +1,12 @@
+// modules
+pub mod image;
+pub mod perlin;
+#[allow(clippy::module_inception)]
+pub mod textures;
+
+// flatten
+pub use image::ImageTexture;
+pub use perlin::NoiseKind;
+pub use perlin::Perlin;
+pub use textures::Texture;
+pub use textures::TextureValue;
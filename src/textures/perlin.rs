@@ -0,0 +1,192 @@
+use std::sync::OnceLock;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::math::*;
+
+use super::TextureValue;
+
+const POINT_COUNT: usize = 256;
+const SEED: u64 = 0x5eed;
+
+// gradient noise over a lattice of random unit vectors
+pub struct Perlin {
+    vectors: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let vectors = (0..POINT_COUNT)
+            .map(|_| {
+                let v = Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                );
+                Vec3::unit_vector(v)
+            })
+            .collect();
+        let mut perm = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        let (perm_x, perm_y, perm_z) = (perm(), perm(), perm());
+        Self {
+            vectors,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    // the table shared by every noise texture, built on first use
+    pub fn shared() -> &'static Perlin {
+        static PERLIN: OnceLock<Perlin> = OnceLock::new();
+        PERLIN.get_or_init(|| Perlin::new(SEED))
+    }
+
+    pub fn noise(&self, p: Vec3) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+        let mask = POINT_COUNT as i64 - 1;
+        let mut c = [[[Vec3::default(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, cell) in row.iter_mut().enumerate() {
+                    let x = self.perm_x[((i + di as i64) & mask) as usize];
+                    let y = self.perm_y[((j + dj as i64) & mask) as usize];
+                    let z = self.perm_z[((k + dk as i64) & mask) as usize];
+                    *cell = self.vectors[x ^ y ^ z];
+                }
+            }
+        }
+        // hermite smoothing of the interpolation weights
+        let (uu, vv, ww) = (
+            u * u * (3.0 - 2.0 * u),
+            v * v * (3.0 - 2.0 * v),
+            w * w * (3.0 - 2.0 * w),
+        );
+        let mut accum = 0.0;
+        for (di, plane) in c.iter().enumerate() {
+            for (dj, row) in plane.iter().enumerate() {
+                for (dk, cell) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * (*cell * weight);
+                }
+            }
+        }
+        accum
+    }
+
+    pub fn turbulence(&self, p: Vec3, depth: usize) -> f64 {
+        let (mut accum, mut weight, mut point) = (0.0, 1.0, p);
+        for _ in 0..depth {
+            accum += weight * self.noise(point);
+            weight *= 0.5;
+            point *= 2.0;
+        }
+        accum.abs()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseKind {
+    Perlin,
+    Turbulence { depth: usize },
+    Marble { depth: usize },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Noise {
+    pub kind: NoiseKind,
+    pub scale: f64,
+    pub color: Vec3,
+}
+
+impl TextureValue for Noise {
+    fn value(&self, _u: f64, _v: f64, p: Vec3) -> Vec3 {
+        let perlin = Perlin::shared();
+        let intensity = match self.kind {
+            NoiseKind::Perlin => 0.5 * (1.0 + perlin.noise(self.scale * p)),
+            NoiseKind::Turbulence { depth } => {
+                perlin.turbulence(self.scale * p, depth)
+            }
+            NoiseKind::Marble { depth } => {
+                let phase =
+                    self.scale * p.z + 10.0 * perlin.turbulence(p, depth);
+                0.5 * (1.0 + phase.sin())
+            }
+        };
+        self.color * intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> impl Iterator<Item = Vec3> {
+        (0..1000).map(|i| {
+            let (x, y, z) = (i % 10, i / 10 % 10, i / 100);
+            Vec3::new(x as f64, y as f64, z as f64) * 0.37
+                - Vec3::new(2.0, 2.0, 2.0)
+        })
+    }
+
+    #[test]
+    fn noise_is_zero_on_the_lattice() {
+        let perlin = Perlin::new(1);
+        for p in [(0.0, 0.0, 0.0), (3.0, -2.0, 7.0), (-5.0, 1.0, -1.0)] {
+            assert_eq!(perlin.noise(Vec3::new(p.0, p.1, p.2)), 0.0);
+        }
+    }
+
+    #[test]
+    fn noise_is_bounded_smooth_and_seeded() {
+        let (a, b) = (Perlin::new(1), Perlin::new(1));
+        let values: Vec<f64> = grid().map(|p| a.noise(p)).collect();
+        assert!(values.iter().all(|n| n.abs() <= 1.0));
+        assert!(values.iter().any(|&n| n > 0.1));
+        assert!(values.iter().any(|&n| n < -0.1));
+        for p in grid() {
+            assert_eq!(a.noise(p), b.noise(p));
+            let nearby = a.noise(p + Vec3::new(1e-6, 1e-6, 1e-6));
+            assert!((a.noise(p) - nearby).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn noise_textures_stay_within_their_color() {
+        let color = Vec3::new(1.0, 0.5, 0.25);
+        let kinds = [
+            NoiseKind::Perlin,
+            NoiseKind::Turbulence { depth: 7 },
+            NoiseKind::Marble { depth: 7 },
+        ];
+        for kind in kinds {
+            let noise = Noise {
+                kind,
+                scale: 4.0,
+                color,
+            };
+            for p in grid() {
+                let value = noise.value(0.0, 0.0, p);
+                let intensity = value.x;
+                assert!((0.0..=1.0).contains(&intensity), "{kind:?} {value}");
+                assert_eq!(value, color * intensity);
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use crate::math::*;
+
+use super::image::ImageTexture;
+use super::perlin::{Noise, NoiseKind};
+
+#[derive(Debug, Clone, Copy)]
+pub struct SolidColor {
+    pub color: Vec3,
+}
+
+// alternates between two colours in a 3d grid of cubes with sides of
+// length scale, independent of the surface parameterisation
+#[derive(Debug, Clone, Copy)]
+pub struct Checker {
+    pub scale: f64,
+    pub even: Vec3,
+    pub odd: Vec3,
+}
+
+#[derive(Debug, Clone)]
+pub enum Texture {
+    Solid(SolidColor),
+    Checker(Checker),
+    Noise(Noise),
+    Image(Arc<ImageTexture>),
+}
+
+impl Texture {
+    pub fn new_solid(r: f64, g: f64, b: f64) -> Self {
+        Self::Solid(SolidColor {
+            color: Vec3::new(r, g, b),
+        })
+    }
+    pub fn new_checker(scale: f64, even: Vec3, odd: Vec3) -> Self {
+        Self::Checker(Checker { scale, even, odd })
+    }
+    pub fn new_noise(kind: NoiseKind, scale: f64, color: Vec3) -> Self {
+        Self::Noise(Noise { kind, scale, color })
+    }
+    pub fn new_image(image: ImageTexture) -> Self {
+        Self::Image(Arc::new(image))
+    }
+}
+
+impl From<Vec3> for Texture {
+    fn from(color: Vec3) -> Self {
+        Self::Solid(SolidColor { color })
+    }
+}
+
+pub trait TextureValue {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3;
+}
+
+impl TextureValue for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+impl TextureValue for Checker {
+    fn value(&self, _u: f64, _v: f64, p: Vec3) -> Vec3 {
+        let cell = |x: f64| (x / self.scale).floor() as i64;
+        match (cell(p.x) + cell(p.y) + cell(p.z)) % 2 == 0 {
+            true => self.even,
+            false => self.odd,
+        }
+    }
+}
+
+impl TextureValue for Texture {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        match self {
+            Self::Solid(tex) => tex.value(u, v, p),
+            Self::Checker(tex) => tex.value(u, v, p),
+            Self::Noise(tex) => tex.value(u, v, p),
+            Self::Image(tex) => tex.value(u, v, p),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checker_alternates_between_cells() {
+        let (even, odd) = (Vec3::new(1.0, 1.0, 1.0), Vec3::default());
+        let checker = Texture::new_checker(0.5, even, odd);
+        let at = |x, y, z| checker.value(0.0, 0.0, Vec3::new(x, y, z));
+        assert_eq!(at(0.25, 0.25, 0.25), even);
+        assert_eq!(at(0.75, 0.25, 0.25), odd);
+        assert_eq!(at(0.75, 0.75, 0.25), even);
+        assert_eq!(at(0.75, 0.75, 0.75), odd);
+        // cells keep alternating across the origin
+        assert_eq!(at(-0.25, 0.25, 0.25), odd);
+        assert_eq!(at(-0.25, -0.25, 0.25), even);
+    }
+}